@@ -0,0 +1,175 @@
+use std::{fmt::Display, io, path::PathBuf};
+
+use jsonrpsee::types::{ErrorObjectOwned, error::INVALID_PARAMS_CODE};
+use serde_json::{Value, json};
+use thiserror::Error as ThisError;
+
+use crate::models::schema::FieldViolation;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub const NOT_FOUND_CODE: i32 = 1001;
+pub const ALREADY_EXISTS_CODE: i32 = 1002;
+pub const INVALID_PASSWORD_CODE: i32 = 1003;
+pub const KEY_NOT_FOUND_CODE: i32 = 1004;
+pub const NO_RECIPIENTS_CODE: i32 = 1005;
+pub const INVALID_METADATA_CODE: i32 = 1006;
+pub const IO_ERROR_CODE: i32 = 1007;
+pub const CRYPTO_ERROR_CODE: i32 = 1008;
+pub const CANCELLED_CODE: i32 = 1009;
+pub const INVALID_QUERY_CODE: i32 = 1010;
+pub const SCHEMA_VIOLATION_CODE: i32 = 1011;
+pub const INTERNAL_ERROR_CODE: i32 = 1099;
+
+#[derive(Debug, ThisError)]
+pub enum Error {
+    #[error("Invalid parameters: {0}")]
+    InvalidParams(String),
+    #[error("Secret '{}' does not exist", .0.display())]
+    NotFound(PathBuf),
+    #[error("Secret '{}' already exists", .0.display())]
+    AlreadyExists(PathBuf),
+    #[error("Password could not unlock any recipient key")]
+    InvalidPassword,
+    #[error("Key '{0}' not found")]
+    KeyNotFound(String),
+    #[error("Saved query '{0}' does not exist")]
+    QueryNotFound(String),
+    #[error("No suitable recipient: {0}")]
+    NoRecipients(String),
+    #[error("Invalid metadata '{}': {reason}", path.display())]
+    InvalidMetadata { path: PathBuf, reason: String },
+    #[error("{context}: {source}")]
+    Io { context: String, source: io::Error },
+    #[error("{context}: {reason}")]
+    Crypto { context: String, reason: String },
+    #[error("Operation was cancelled")]
+    Cancelled,
+    #[error("Invalid query at position {position}: {message}")]
+    InvalidQuery { position: usize, message: String },
+    #[error(
+        "Metadata for '{}' violates its schema: {}",
+        path.display(),
+        violations
+            .iter()
+            .map(|v| format!("'{}' {}", v.field, v.reason))
+            .collect::<Vec<_>>()
+            .join("; ")
+    )]
+    SchemaViolation {
+        path: PathBuf,
+        violations: Vec<FieldViolation>,
+    },
+    #[error("{0}")]
+    Internal(String),
+}
+
+impl Error {
+    pub fn code(&self) -> i32 {
+        match self {
+            Error::InvalidParams(_) => INVALID_PARAMS_CODE,
+            Error::NotFound(_) => NOT_FOUND_CODE,
+            Error::AlreadyExists(_) => ALREADY_EXISTS_CODE,
+            Error::InvalidPassword => INVALID_PASSWORD_CODE,
+            Error::KeyNotFound(_) => KEY_NOT_FOUND_CODE,
+            Error::QueryNotFound(_) => NOT_FOUND_CODE,
+            Error::NoRecipients(_) => NO_RECIPIENTS_CODE,
+            Error::InvalidMetadata { .. } => INVALID_METADATA_CODE,
+            Error::Io { .. } => IO_ERROR_CODE,
+            Error::Crypto { .. } => CRYPTO_ERROR_CODE,
+            Error::Cancelled => CANCELLED_CODE,
+            Error::InvalidQuery { .. } => INVALID_QUERY_CODE,
+            Error::SchemaViolation { .. } => SCHEMA_VIOLATION_CODE,
+            Error::Internal(_) => INTERNAL_ERROR_CODE,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::InvalidParams(_) => "invalid_params",
+            Error::NotFound(_) => "not_found",
+            Error::AlreadyExists(_) => "already_exists",
+            Error::InvalidPassword => "invalid_password",
+            Error::KeyNotFound(_) => "key_not_found",
+            Error::QueryNotFound(_) => "not_found",
+            Error::NoRecipients(_) => "no_recipients",
+            Error::InvalidMetadata { .. } => "invalid_metadata",
+            Error::Io { .. } => "io",
+            Error::Crypto { .. } => "crypto",
+            Error::Cancelled => "cancelled",
+            Error::InvalidQuery { .. } => "invalid_query",
+            Error::SchemaViolation { .. } => "schema_violation",
+            Error::Internal(_) => "internal",
+        }
+    }
+
+    pub fn data(&self) -> Value {
+        let mut data = json!({ "kind": self.kind() });
+
+        match self {
+            Error::NotFound(path) | Error::AlreadyExists(path) => {
+                data["path"] = json!(path);
+            }
+            Error::KeyNotFound(fingerprint) => {
+                data["fingerprint"] = json!(fingerprint);
+            }
+            Error::QueryNotFound(name) => {
+                data["name"] = json!(name);
+            }
+            Error::InvalidMetadata { path, reason } => {
+                data["path"] = json!(path);
+                data["reason"] = json!(reason);
+            }
+            Error::InvalidQuery { position, message } => {
+                data["position"] = json!(position);
+                data["reason"] = json!(message);
+            }
+            Error::SchemaViolation { path, violations } => {
+                data["path"] = json!(path);
+                data["violations"] = json!(violations);
+            }
+            Error::Io { source, .. } => {
+                data["io_kind"] = json!(source.kind().to_string());
+            }
+            _ => {}
+        }
+
+        data
+    }
+}
+
+impl From<Error> for ErrorObjectOwned {
+    fn from(error: Error) -> Self {
+        ErrorObjectOwned::owned(
+            error.code(),
+            error.to_string(),
+            Some(error.data()),
+        )
+    }
+}
+
+pub(crate) trait IoContext<T> {
+    fn io_context<C: Into<String>>(self, context: C) -> Result<T>;
+}
+
+impl<T> IoContext<T> for std::result::Result<T, io::Error> {
+    fn io_context<C: Into<String>>(self, context: C) -> Result<T> {
+        self.map_err(|source| Error::Io {
+            context: context.into(),
+            source,
+        })
+    }
+}
+
+pub(crate) trait CryptoContext<T> {
+    fn crypto_context<C: Into<String>>(self, context: C) -> Result<T>;
+}
+
+impl<T, E: Display> CryptoContext<T> for std::result::Result<T, E> {
+    fn crypto_context<C: Into<String>>(self, context: C) -> Result<T> {
+        self.map_err(|e| Error::Crypto {
+            context: context.into(),
+            reason: e.to_string(),
+        })
+    }
+}
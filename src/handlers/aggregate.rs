@@ -0,0 +1,361 @@
+use super::{Access, Context, Handler};
+use chrono::{DateTime, Datelike, Utc};
+use passd::{
+    models::{
+        filter::{Filter, combine_filter},
+        metadata::Metadata,
+        unlock::MetadataView,
+    },
+    utils::{memory::SecretString, time::parse_datetime},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, path::Path, sync::Arc};
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interval {
+    Day,
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Facet {
+    pub field: String,
+    pub interval: Option<Interval>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AggregateParams {
+    pub filter: Option<Filter>,
+    pub query: Option<String>,
+    pub facets: Vec<Facet>,
+    pub password: Option<SecretString>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Bucket {
+    pub key: String,
+    pub count: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct FacetResult {
+    pub field: String,
+    pub buckets: Vec<Bucket>,
+    pub missing: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AggregateResponse {
+    pub total: usize,
+    pub facets: Vec<FacetResult>,
+}
+
+impl Interval {
+    fn bucket(self, date: DateTime<Utc>) -> String {
+        match self {
+            Interval::Day => date.format("%Y-%m-%d").to_string(),
+            Interval::Week => {
+                let week = date.iso_week();
+
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            Interval::Month => date.format("%Y-%m").to_string(),
+            Interval::Quarter => {
+                format!("{}-Q{}", date.year(), date.month0() / 3 + 1)
+            }
+            Interval::Year => date.year().to_string(),
+        }
+    }
+}
+
+fn bucket_key(value: &Value, interval: Option<Interval>) -> Option<String> {
+    match (value, interval) {
+        (Value::Null, _) => None,
+        (Value::String(s), Some(interval)) => {
+            parse_datetime(s).map(|date| interval.bucket(date))
+        }
+        (_, Some(_)) => None,
+        (Value::String(s), None) => Some(s.clone()),
+        (value, None) => Some(value.to_string()),
+    }
+}
+
+fn aggregate(facet: &Facet, hits: &[&Metadata]) -> FacetResult {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    let mut missing = 0;
+
+    for metadata in hits {
+        let value = metadata.get_field(&facet.field).ok().flatten();
+        let keys: Vec<String> = match &value {
+            Some(Value::Array(items)) => items
+                .iter()
+                .filter_map(|item| bucket_key(item, facet.interval))
+                .collect(),
+            Some(value) => {
+                bucket_key(value, facet.interval).into_iter().collect()
+            }
+            None => Vec::new(),
+        };
+
+        if keys.is_empty() {
+            missing += 1;
+        }
+
+        for key in keys {
+            *counts.entry(key).or_default() += 1;
+        }
+    }
+
+    let mut buckets: Vec<Bucket> = counts
+        .into_iter()
+        .map(|(key, count)| Bucket { key, count })
+        .collect();
+
+    if facet.interval.is_none() {
+        buckets.sort_by(|a, b| b.count.cmp(&a.count).then(a.key.cmp(&b.key)));
+    }
+
+    if let Some(limit) = facet.limit {
+        buckets.truncate(limit);
+    }
+
+    FacetResult {
+        field: facet.field.clone(),
+        buckets,
+        missing,
+    }
+}
+
+pub struct Aggregate;
+
+impl Handler for Aggregate {
+    type Params = AggregateParams;
+    type Response = AggregateResponse;
+    const ACCESS: Access = Access::Shared;
+
+    fn handle(
+        params: AggregateParams,
+        ctx: &Context,
+    ) -> passd::Result<AggregateResponse> {
+        let filter = combine_filter(params.filter, params.query)?;
+        let view = MetadataView::new(Arc::clone(&ctx.config), params.password);
+        let result = ctx.secret_manager().find(
+            filter.map(|filter| {
+                let view = &view;
+
+                move |meta: &Metadata| {
+                    view.with(meta, |meta| filter.matches(meta))
+                }
+            }),
+            None::<fn(&Metadata, &Metadata) -> _>,
+            None::<fn(&Path, &Metadata) -> _>,
+            None,
+            None,
+            &ctx.cancel,
+        )?;
+        let unlocked: Vec<Metadata> = result
+            .hits
+            .iter()
+            .map(|(_, metadata)| view.with(metadata, Metadata::clone))
+            .collect();
+        let hits: Vec<&Metadata> = unlocked.iter().collect();
+
+        view.check()?;
+
+        let mut facets = Vec::with_capacity(params.facets.len());
+
+        for facet in &params.facets {
+            ctx.cancel.check()?;
+            facets.push(aggregate(facet, &hits));
+        }
+
+        Ok(AggregateResponse {
+            total: result.total,
+            facets,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::tests::{context, sealed_vault, write_metadata};
+    use passd::models::metadata::BaseMetadata;
+    use serde_json::json;
+    use std::path::PathBuf;
+
+    fn aggregate(ctx: &Context, params: Value) -> Value {
+        let params = serde_json::from_value(params).unwrap();
+
+        serde_json::to_value(Aggregate::handle(params, ctx).unwrap()).unwrap()
+    }
+
+    fn vault(dir: &Path) -> Context {
+        let ctx = context(dir);
+
+        for (path, category, tags, updated_at) in [
+            (
+                "a",
+                Some("work"),
+                vec!["mail", "prod"],
+                "2025-01-15T00:00:00Z",
+            ),
+            ("b", Some("work"), vec!["prod"], "2025-03-31T23:00:00Z"),
+            ("c", Some("home"), vec!["mail"], "2025-04-01T00:00:00Z"),
+            ("d", None, vec![], "2025-11-30T00:00:00Z"),
+        ] {
+            write_metadata(
+                &ctx,
+                &Metadata {
+                    path: PathBuf::from(path),
+                    template: BaseMetadata {
+                        category: category.map(str::to_string),
+                        tags: Some(
+                            tags.into_iter().map(str::to_string).collect(),
+                        ),
+                        ..BaseMetadata::default()
+                    },
+                    updated_at: updated_at.parse().unwrap(),
+                    ..Metadata::default()
+                },
+            );
+        }
+
+        ctx
+    }
+
+    #[test]
+    fn facets_count_values_and_each_tag() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = vault(dir.path());
+
+        assert_eq!(
+            aggregate(
+                &ctx,
+                json!({
+                    "facets": [
+                        { "field": "category" },
+                        { "field": "tags" },
+                        { "field": "tags", "limit": 1 },
+                    ],
+                })
+            ),
+            json!({
+                "total": 4,
+                "facets": [
+                    {
+                        "field": "category",
+                        "buckets": [
+                            { "key": "work", "count": 2 },
+                            { "key": "home", "count": 1 },
+                        ],
+                        "missing": 1,
+                    },
+                    {
+                        "field": "tags",
+                        "buckets": [
+                            { "key": "mail", "count": 2 },
+                            { "key": "prod", "count": 2 },
+                        ],
+                        "missing": 1,
+                    },
+                    {
+                        "field": "tags",
+                        "buckets": [{ "key": "mail", "count": 2 }],
+                        "missing": 1,
+                    },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn dates_group_into_ordered_histograms_after_filtering() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = vault(dir.path());
+
+        assert_eq!(
+            aggregate(
+                &ctx,
+                json!({
+                    "facets": [
+                        { "field": "updated_at", "interval": "quarter" },
+                        { "field": "updated_at", "interval": "month" },
+                    ],
+                })
+            )["facets"],
+            json!([
+                {
+                    "field": "updated_at",
+                    "buckets": [
+                        { "key": "2025-Q1", "count": 2 },
+                        { "key": "2025-Q2", "count": 1 },
+                        { "key": "2025-Q4", "count": 1 },
+                    ],
+                    "missing": 0,
+                },
+                {
+                    "field": "updated_at",
+                    "buckets": [
+                        { "key": "2025-01", "count": 1 },
+                        { "key": "2025-03", "count": 1 },
+                        { "key": "2025-04", "count": 1 },
+                        { "key": "2025-11", "count": 1 },
+                    ],
+                    "missing": 0,
+                },
+            ])
+        );
+        assert_eq!(
+            aggregate(
+                &ctx,
+                json!({
+                    "query": "tags:prod",
+                    "facets": [{ "field": "updated_at", "interval": "year" }],
+                })
+            ),
+            json!({
+                "total": 2,
+                "facets": [{
+                    "field": "updated_at",
+                    "buckets": [{ "key": "2025", "count": 2 }],
+                    "missing": 0,
+                }],
+            })
+        );
+    }
+
+    #[test]
+    fn sealed_fields_are_faceted_once_unlocked() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = sealed_vault(dir.path());
+        let params = serde_json::from_value(json!({
+            "query": "owner:*",
+            "facets": [{ "field": "owner" }],
+            "password": "alice-password",
+        }))
+        .unwrap();
+        let response = Aggregate::handle(params, &ctx).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(response).unwrap(),
+            json!({
+                "total": 2,
+                "facets": [{
+                    "field": "owner",
+                    "buckets": [
+                        { "key": "alice", "count": 1 },
+                        { "key": "carol", "count": 1 },
+                    ],
+                    "missing": 0,
+                }],
+            })
+        );
+    }
+}
@@ -0,0 +1,38 @@
+use super::{Access, Context, Handler};
+use passd::{
+    models::{
+        filter::{Filter, combine_filter},
+        metadata::Metadata,
+        secret_manager::DiagnosticResult,
+    },
+    utils::memory::SecretString,
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct AuditParams {
+    pub password: Option<SecretString>,
+    pub filter: Option<Filter>,
+    pub query: Option<String>,
+}
+
+pub struct Audit;
+
+impl Handler for Audit {
+    type Params = AuditParams;
+    type Response = Vec<DiagnosticResult>;
+    const ACCESS: Access = Access::Shared;
+
+    fn handle(
+        params: AuditParams,
+        ctx: &Context,
+    ) -> passd::Result<Vec<DiagnosticResult>> {
+        let filter = combine_filter(params.filter, params.query)?;
+
+        ctx.secret_manager().audit(
+            params.password.unwrap_or_default(),
+            filter.map(|filter| move |meta: &Metadata| filter.matches(meta)),
+            &ctx.cancel,
+        )
+    }
+}
@@ -1,71 +1,35 @@
-use crate::Config;
-use jsonrpsee::Extensions;
-use jsonrpsee::types::{ErrorObject, Params};
-use log::{error, info};
-use passd::models::secret::Secret;
+use super::{Access, Context, Handler};
+use passd::utils::memory::SecretString;
 use serde::Deserialize;
-use std::path::PathBuf;
-use std::sync::Arc;
 
 #[derive(Debug, Deserialize)]
-struct CloneParams {
+pub struct CloneParams {
     from_path: String,
     to_path: String,
-    public_key: String,
-    private_key: Option<String>,
-    password: Option<String>,
+    recipients: Vec<String>,
+    password: Option<SecretString>,
 }
 
-pub fn handler(
-    params: Params,
-    ctx: &Arc<Config>,
-    _ext: &Extensions,
-) -> Result<String, ErrorObject<'static>> {
-    let clone_params: CloneParams = params.parse().map_err(|e| {
-        error!("Failed to parse parameters: {}", e);
+pub struct CloneTo;
 
-        ErrorObject::owned(
-            jsonrpsee::types::error::INVALID_PARAMS_CODE,
-            "Invalid parameters",
-            Some(format!("Failed to parse parameters: {}", e)),
-        )
-    })?;
+impl Handler for CloneTo {
+    type Params = CloneParams;
+    type Response = String;
+    const ACCESS: Access = Access::Exclusive;
 
-    match (Secret {
-        relative_path: clone_params.from_path.clone().into(),
-        config: Arc::clone(ctx),
-    })
-    .clone_to(
-        PathBuf::from(&clone_params.to_path),
-        &clone_params.public_key,
-        clone_params.private_key.as_deref(),
-        clone_params.password.unwrap_or_default().as_str(),
-    ) {
-        Ok(_) => {
-            info!(
-                "Successfully cloned secret from {} to {}",
-                clone_params.from_path, clone_params.to_path
-            );
+    fn handle(params: CloneParams, ctx: &Context) -> passd::Result<String> {
+        let recipients: Vec<&str> =
+            params.recipients.iter().map(String::as_str).collect();
 
-            Ok(format!(
-                "Successfully cloned secret from {} to {}",
-                clone_params.from_path, clone_params.to_path
-            ))
-        }
-        Err(e) => {
-            error!(
-                "Failed to clone secret from {} to {}: {}",
-                clone_params.from_path, clone_params.to_path, e
-            );
+        ctx.secret(params.from_path.clone()).clone_to(
+            params.to_path.clone().into(),
+            &recipients,
+            params.password.unwrap_or_default().as_str(),
+        )?;
 
-            Err(ErrorObject::owned(
-                jsonrpsee::types::error::INTERNAL_ERROR_CODE,
-                format!(
-                    "Failed to clone secret from {} to {}",
-                    clone_params.from_path, clone_params.to_path
-                ),
-                Some(e.to_string()),
-            ))
-        }
+        Ok(format!(
+            "Successfully cloned secret from {} to {}",
+            params.from_path, params.to_path
+        ))
     }
 }
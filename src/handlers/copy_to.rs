@@ -1,63 +1,26 @@
-use crate::Config;
-use jsonrpsee::Extensions;
-use jsonrpsee::types::{ErrorObject, Params};
-use log::{error, info};
-use passd::models::secret::Secret;
+use super::{Access, Context, Handler};
 use serde::Deserialize;
-use std::sync::Arc;
 
 #[derive(Debug, Deserialize)]
-struct CopyParams {
+pub struct CopyParams {
     from_path: String,
     to_path: String,
 }
 
-pub fn handler(
-    params: Params,
-    ctx: &Arc<Config>,
-    _ext: &Extensions,
-) -> Result<String, ErrorObject<'static>> {
-    let copy_params: CopyParams = params.parse().map_err(|e| {
-        error!("Failed to parse parameters: {}", e);
+pub struct CopyTo;
 
-        ErrorObject::owned(
-            jsonrpsee::types::error::INVALID_PARAMS_CODE,
-            "Invalid parameters",
-            Some(format!("Failed to parse parameters: {}", e)),
-        )
-    })?;
+impl Handler for CopyTo {
+    type Params = CopyParams;
+    type Response = String;
+    const ACCESS: Access = Access::Exclusive;
 
-    match (Secret {
-        relative_path: copy_params.from_path.clone().into(),
-        config: Arc::clone(ctx),
-    })
-    .copy_to(copy_params.to_path.clone().into())
-    {
-        Ok(_) => {
-            info!(
-                "Successfully copyd secret from {} to {}",
-                copy_params.from_path, copy_params.to_path
-            );
+    fn handle(params: CopyParams, ctx: &Context) -> passd::Result<String> {
+        ctx.secret(params.from_path.clone())
+            .copy_to(params.to_path.clone().into())?;
 
-            Ok(format!(
-                "Successfully copyd secret from {} to {}",
-                copy_params.from_path, copy_params.to_path
-            ))
-        }
-        Err(e) => {
-            error!(
-                "Failed to copy secret from {} to {}: {}",
-                copy_params.from_path, copy_params.to_path, e
-            );
-
-            Err(ErrorObject::owned(
-                jsonrpsee::types::error::INTERNAL_ERROR_CODE,
-                format!(
-                    "Failed to copy secret from {} to {}",
-                    copy_params.from_path, copy_params.to_path
-                ),
-                Some(e.to_string()),
-            ))
-        }
+        Ok(format!(
+            "Successfully copied secret from {} to {}",
+            params.from_path, params.to_path
+        ))
     }
 }
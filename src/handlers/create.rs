@@ -1,61 +1,64 @@
-use crate::Config;
-use jsonrpsee::{
-    Extensions,
-    types::{ErrorObject, Params},
+use super::{
+    Access, Context, Handler,
+    generate::{WriteResponse, resolve_content},
+};
+use passd::{
+    Error,
+    models::{generator::GenerateOptions, metadata::BaseMetadata},
+    utils::memory::SecretString,
 };
-use log::{error, info};
-use passd::models::{metadata::BaseMetadata, secret::Secret};
 use serde::Deserialize;
-use std::sync::Arc;
 
 #[derive(Debug, Deserialize)]
-struct CreateParams {
+pub struct CreateParams {
     path: String,
-    content: String,
+    content: Option<SecretString>,
+    generate: Option<GenerateOptions>,
+    #[serde(default)]
+    reveal: bool,
     metadata: BaseMetadata,
-    public_key: Option<String>,
+    #[serde(default)]
+    encrypt_fields: Vec<String>,
+    recipients: Vec<String>,
 }
 
-pub fn handler(
-    params: Params,
-    ctx: &Arc<Config>,
-    _ext: &Extensions,
-) -> Result<String, ErrorObject<'static>> {
-    let create_params: CreateParams = params.parse().map_err(|e| {
-        error!("Failed to parse parameters: {}", e);
+pub struct Create;
 
-        ErrorObject::owned(
-            jsonrpsee::types::error::INVALID_PARAMS_CODE,
-            "Invalid parameters",
-            Some(format!("Failed to parse parameters: {}", e)),
-        )
-    })?;
+impl Handler for Create {
+    type Params = CreateParams;
+    type Response = WriteResponse;
+    const ACCESS: Access = Access::Exclusive;
 
-    match (Secret {
-        relative_path: create_params.path.clone().into(),
-        config: Arc::clone(ctx),
-    })
-    .create(
-        &create_params.content,
-        &create_params.metadata,
-        create_params.public_key.as_deref(),
-    ) {
-        Ok(_) => {
-            info!("Successfully created secret {}", create_params.path);
+    fn handle(
+        params: CreateParams,
+        ctx: &Context,
+    ) -> passd::Result<WriteResponse> {
+        let generated = params.generate.is_some();
+        let content =
+            resolve_content(params.content, params.generate.as_ref())?
+                .ok_or_else(|| {
+                    Error::InvalidParams(
+                        "Either content or generate must be given".to_string(),
+                    )
+                })?;
+        let recipients: Vec<&str> =
+            params.recipients.iter().map(String::as_str).collect();
+        let metadata = match ctx.config.template_for(&params.metadata) {
+            Some(template) => params.metadata.with_template(template),
+            None => params.metadata,
+        };
 
-            Ok(format!(
-                "Successfully created secret {}",
-                create_params.path
-            ))
-        }
-        Err(e) => {
-            error!("Failed to create secret {}: {}", create_params.path, e);
+        ctx.secret(params.path.clone()).create(
+            &content,
+            &metadata,
+            &params.encrypt_fields,
+            &recipients,
+        )?;
 
-            Err(ErrorObject::owned(
-                jsonrpsee::types::error::INTERNAL_ERROR_CODE,
-                format!("Failed to create secret {}", create_params.path),
-                Some(e.to_string()),
-            ))
-        }
+        Ok(WriteResponse::new(
+            format!("Successfully created secret {}", params.path),
+            generated.then_some(content),
+            params.reveal,
+        ))
     }
 }
@@ -1,53 +1,24 @@
-use crate::Config;
-use jsonrpsee::Extensions;
-use jsonrpsee::types::{ErrorObject, Params};
-use log::{error, info};
-use passd::models::secret::Secret;
+use super::{Access, Context, Handler};
+use passd::utils::memory::SecretString;
 use serde::Deserialize;
-use std::sync::Arc;
 
 #[derive(Debug, Deserialize)]
-struct DeleteParams {
+pub struct DeleteParams {
     path: String,
+    password: Option<SecretString>,
 }
 
-pub fn handler(
-    params: Params,
-    ctx: &Arc<Config>,
-    _ext: &Extensions,
-) -> Result<String, ErrorObject<'static>> {
-    let delete_params: DeleteParams = params.parse().map_err(|e| {
-        error!("Failed to parse parameters: {}", e);
+pub struct Delete;
 
-        ErrorObject::owned(
-            jsonrpsee::types::error::INVALID_PARAMS_CODE,
-            "Invalid parameters",
-            Some(format!("Failed to parse parameters: {}", e)),
-        )
-    })?;
+impl Handler for Delete {
+    type Params = DeleteParams;
+    type Response = String;
+    const ACCESS: Access = Access::Exclusive;
 
-    match (Secret {
-        relative_path: delete_params.path.clone().into(),
-        config: Arc::clone(ctx),
-    })
-    .remove()
-    {
-        Ok(_) => {
-            info!("Successfully deleted secret {}", delete_params.path);
+    fn handle(params: DeleteParams, ctx: &Context) -> passd::Result<String> {
+        ctx.secret(params.path.clone())
+            .remove(params.password.unwrap_or_default().as_str())?;
 
-            Ok(format!(
-                "Successfully deleted secret {}",
-                delete_params.path
-            ))
-        }
-        Err(e) => {
-            error!("Failed to delete secret {}: {}", delete_params.path, e);
-
-            Err(ErrorObject::owned(
-                jsonrpsee::types::error::INTERNAL_ERROR_CODE,
-                format!("Failed to delete secret {}", delete_params.path),
-                Some(e.to_string()),
-            ))
-        }
+        Ok(format!("Successfully deleted secret {}", params.path))
     }
 }
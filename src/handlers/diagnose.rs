@@ -1,33 +1,35 @@
-use crate::Config;
-use jsonrpsee::{
-    Extensions,
-    types::{ErrorObject, Params},
-};
-use passd::models::secret_manager::Secrets;
-use serde_json::Value;
-use std::sync::Arc;
+use super::{Access, Context, Handler};
+use passd::models::secret_manager::DiagnosticResult;
+use serde::Deserialize;
 
-pub fn handler(
-    _params: Params,
-    ctx: &Arc<Config>,
-    _ext: &Extensions,
-) -> Result<Value, ErrorObject<'static>> {
-    match (Secrets {
-        config: Arc::clone(ctx),
-    }
-    .diagnose())
-    {
-        Ok(diagnostics) => serde_json::to_value(diagnostics).map_err(|e| {
-            ErrorObject::owned(
-                jsonrpsee::types::error::INTERNAL_ERROR_CODE,
-                "Failed to serialize diagnostics",
-                Some(e.to_string()),
-            )
-        }),
-        Err(e) => Err(ErrorObject::owned(
-            jsonrpsee::types::error::INTERNAL_ERROR_CODE,
-            format!("Unexpected diagnostics error {e}"),
-            Some(e.to_string()),
-        )),
+#[derive(Debug, Deserialize)]
+pub struct DiagnoseParams {}
+
+pub struct Diagnose;
+
+impl Handler for Diagnose {
+    type Params = DiagnoseParams;
+    type Response = Vec<DiagnosticResult>;
+    const ACCESS: Access = Access::Shared;
+
+    fn handle(
+        _params: DiagnoseParams,
+        ctx: &Context,
+    ) -> passd::Result<Vec<DiagnosticResult>> {
+        let (mut diagnostics, mark) = ctx
+            .watcher
+            .as_ref()
+            .map(|watcher| watcher.diagnostics())
+            .unwrap_or_default();
+
+        diagnostics.extend(ctx.secret_manager().diagnose(&ctx.cancel)?);
+
+        // Only dropped once they are part of a response, and only the ones
+        // this call saw.
+        if let Some(watcher) = &ctx.watcher {
+            watcher.acknowledge(mark);
+        }
+
+        Ok(diagnostics)
     }
 }
@@ -0,0 +1,147 @@
+use super::{Access, Context, Handler};
+use passd::{
+    Error,
+    models::{
+        filter::{Filter, combine_filter},
+        metadata::Metadata,
+        secret_manager::ExpiringSecret,
+        unlock::MetadataView,
+    },
+    utils::{memory::SecretString, time::parse_duration},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+const DEFAULT_WINDOW: &str = "30d";
+
+#[derive(Debug, Deserialize)]
+pub struct ExpiringParams {
+    pub within: Option<String>,
+    pub filter: Option<Filter>,
+    pub query: Option<String>,
+    pub password: Option<SecretString>,
+}
+
+pub struct Expiring;
+
+impl Handler for Expiring {
+    type Params = ExpiringParams;
+    type Response = Vec<ExpiringSecret>;
+    const ACCESS: Access = Access::Shared;
+
+    fn handle(
+        params: ExpiringParams,
+        ctx: &Context,
+    ) -> passd::Result<Vec<ExpiringSecret>> {
+        let within = params.within.as_deref().unwrap_or(DEFAULT_WINDOW);
+        let within = parse_duration(within).ok_or_else(|| {
+            Error::InvalidParams(format!("Invalid duration '{within}'"))
+        })?;
+        let filter = combine_filter(params.filter, params.query)?;
+        let view = MetadataView::new(Arc::clone(&ctx.config), params.password);
+        let expiring = ctx.secret_manager().expiring(
+            within,
+            filter.map(|filter| move |meta: &Metadata| filter.matches(meta)),
+            &view,
+            &ctx.cancel,
+        )?;
+
+        view.check()?;
+
+        Ok(expiring)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::tests::{context, sealed_vault, write_metadata};
+    use chrono::{Duration, Utc};
+    use passd::models::metadata::BaseMetadata;
+    use serde_json::{Value, json};
+    use std::path::PathBuf;
+
+    fn expiring(ctx: &Context, password: Option<&str>) -> Vec<PathBuf> {
+        let params = serde_json::from_value(json!({
+            "within": "7d",
+            "password": password,
+        }))
+        .unwrap();
+
+        Expiring::handle(params, ctx)
+            .unwrap()
+            .into_iter()
+            .map(|secret| secret.path)
+            .collect()
+    }
+
+    #[test]
+    fn sealed_expiry_dates_count_once_unlocked() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = sealed_vault(dir.path());
+
+        assert_eq!(expiring(&ctx, None), [PathBuf::from("c")]);
+        assert_eq!(
+            expiring(&ctx, Some("bob-password")),
+            [PathBuf::from("b"), PathBuf::from("c")]
+        );
+    }
+
+    #[test]
+    fn deadlines_within_the_window_are_listed_soonest_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = context(dir.path());
+        let now = Utc::now();
+
+        for (path, expires_in, rotate_every, created_ago) in [
+            ("later", Some(60), None, 0),
+            ("soon", Some(3), None, 0),
+            ("stale", None, Some("90d"), 100),
+            ("fresh", None, Some("90d"), 10),
+            ("none", None, None, 400),
+        ] {
+            write_metadata(
+                &ctx,
+                &Metadata {
+                    path: PathBuf::from(path),
+                    template: BaseMetadata {
+                        expires_at: expires_in
+                            .map(|days| now + Duration::days(days)),
+                        rotate_every: rotate_every.map(str::to_string),
+                        ..BaseMetadata::default()
+                    },
+                    created_at: now - Duration::days(created_ago),
+                    ..Metadata::default()
+                },
+            );
+        }
+
+        let listed = |within: &str| -> Vec<Value> {
+            let params =
+                serde_json::from_value(json!({ "within": within })).unwrap();
+
+            Expiring::handle(params, &ctx)
+                .unwrap()
+                .into_iter()
+                .map(|secret| json!([secret.path, secret.kind, secret.expired]))
+                .collect()
+        };
+
+        assert_eq!(
+            listed("30d"),
+            [
+                json!(["stale", "rotation", true]),
+                json!(["soon", "expiry", false]),
+            ]
+        );
+        assert_eq!(
+            listed("90d"),
+            [
+                json!(["stale", "rotation", true]),
+                json!(["soon", "expiry", false]),
+                json!(["later", "expiry", false]),
+                json!(["fresh", "rotation", false]),
+            ]
+        );
+    }
+}
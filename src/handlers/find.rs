@@ -1,40 +1,21 @@
-use crate::Config;
-use jsonrpsee::{
-    Extensions,
-    types::{ErrorObject, Params},
+use super::{Access, Context, Handler};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use passd::{
+    Error,
+    models::{
+        filter::{Filter, combine_filter, total_order},
+        metadata::Metadata,
+        unlock::MetadataView,
+    },
+    utils::memory::SecretString,
 };
-use log::error;
-use passd::models::{metadata::Metadata, secret_manager::Secrets};
-use regex::Regex;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use std::{cmp::Ordering, path::PathBuf, sync::Arc};
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase", tag = "op", content = "value")]
-pub enum Operator {
-    Eq(Value),
-    Gt(Value),
-    Lt(Value),
-    Contains(Value),
-    Regex(String),
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct FieldCondition {
-    pub field: String,
-    #[serde(flatten)]
-    pub operator: Operator,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum Filter {
-    Not(Box<Filter>),
-    And(Vec<Filter>),
-    Or(Vec<Filter>),
-    Condition(FieldCondition),
-}
+use serde_json::{Map, Value};
+use std::{
+    cmp::Ordering,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -52,147 +33,356 @@ pub struct SortField {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct QueryRequest {
     pub filter: Option<Filter>,
+    pub query: Option<String>,
     pub sort: Option<Vec<SortField>>,
     pub limit: Option<u32>,
     pub offset: Option<u32>,
+    pub cursor: Option<String>,
+    pub fields: Option<Vec<String>>,
+    #[serde(default)]
+    pub paged: bool,
+    #[serde(default, skip_serializing)]
+    pub password: Option<SecretString>,
 }
 
-fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
-    match (a, b) {
-        (Value::Number(a), Value::Number(b)) => {
-            if let (Some(a), Some(b)) = (a.as_f64(), b.as_f64()) {
-                a.partial_cmp(&b)
-            } else {
-                None
+#[derive(Debug, Serialize, Deserialize)]
+struct Cursor {
+    keys: Vec<Value>,
+    path: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum FindResponse {
+    Paths(Vec<PathBuf>),
+    Page {
+        total: usize,
+        paths: Vec<PathBuf>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        next_cursor: Option<String>,
+    },
+    Hits {
+        total: usize,
+        hits: Vec<Map<String, Value>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        next_cursor: Option<String>,
+    },
+}
+
+impl Cursor {
+    fn encode(&self) -> passd::Result<String> {
+        let json = serde_json::to_vec(self).map_err(|e| {
+            Error::Internal(format!("Failed to encode cursor: {e}"))
+        })?;
+
+        Ok(URL_SAFE_NO_PAD.encode(json))
+    }
+
+    fn decode(cursor: &str) -> passd::Result<Self> {
+        URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok())
+            .ok_or_else(|| Error::InvalidParams("Invalid cursor".to_string()))
+    }
+}
+
+// Keys come from the same unlocked view the filter sees, so sealed fields
+// sort and page like plaintext ones.
+fn sort_keys(
+    view: &MetadataView,
+    meta: &Metadata,
+    sort_fields: &[SortField],
+) -> Vec<Value> {
+    view.with(meta, |meta| {
+        sort_fields
+            .iter()
+            .map(|field| {
+                meta.get_field(&field.field)
+                    .ok()
+                    .flatten()
+                    .unwrap_or(Value::Null)
+            })
+            .collect()
+    })
+}
+
+fn compare_keys(
+    a: &[Value],
+    b: &[Value],
+    sort_fields: &[SortField],
+) -> Ordering {
+    for ((a_val, b_val), field) in a.iter().zip(b).zip(sort_fields) {
+        let cmp = total_order(a_val, b_val);
+        // Secrets without the field stay last whichever way the sort goes.
+        let ord = match field.direction {
+            SortDirection::Desc if !a_val.is_null() && !b_val.is_null() => {
+                cmp.reverse()
             }
+            _ => cmp,
+        };
+
+        if ord != Ordering::Equal {
+            return ord;
         }
-        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
-        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
-        _ => None,
     }
+
+    Ordering::Equal
 }
 
-impl Filter {
-    pub fn matches(&self, meta: &Metadata) -> bool {
-        match self {
-            Filter::Not(filter) => !filter.matches(meta),
-            Filter::And(filters) => filters.iter().all(|f| f.matches(meta)),
-            Filter::Or(filters) => filters.iter().any(|f| f.matches(meta)),
-            Filter::Condition(cond) => {
-                let value = meta.get_field(&cond.field);
-
-                match &cond.operator {
-                    Operator::Eq(target) => match value {
-                        Ok(Some(val)) => val == *target,
-                        Ok(None) => false,
-                        Err(_) => false,
-                    },
-                    Operator::Gt(target) => match value {
-                        Ok(Some(val)) => {
-                            compare_values(&val, target)
-                                == Some(Ordering::Greater)
-                        }
-                        Ok(None) => false,
-                        Err(_) => false,
-                    },
-                    Operator::Lt(target) => match value {
-                        Ok(Some(val)) => {
-                            compare_values(&val, target) == Some(Ordering::Less)
-                        }
-                        Ok(None) => false,
-                        Err(_) => false,
-                    },
-                    Operator::Contains(needle) => match value {
-                        Ok(Some(val)) => match (&val, needle) {
-                            (Value::Array(haystack), Value::Array(needles)) => {
-                                needles.iter().any(|n| haystack.contains(n))
-                            }
-                            (Value::Array(haystack), _) => {
-                                haystack.contains(needle)
-                            }
-                            (Value::String(hay), Value::String(needle)) => {
-                                hay.contains(needle)
-                            }
-                            _ => val == *needle,
-                        },
-                        Ok(None) => false,
-                        Err(_) => false,
-                    },
-                    Operator::Regex(pattern) => match value {
-                        Ok(Some(val)) => val.as_str().map_or(false, |s| {
-                            Regex::new(pattern)
-                                .map_or(false, |re| re.is_match(s))
-                        }),
-                        Ok(None) => false,
-                        Err(_) => false,
-                    },
+pub struct Find;
+
+impl Handler for Find {
+    type Params = QueryRequest;
+    type Response = FindResponse;
+    const ACCESS: Access = Access::Shared;
+
+    fn handle(req: QueryRequest, ctx: &Context) -> passd::Result<FindResponse> {
+        let filter = combine_filter(req.filter, req.query)?;
+        let cursor = req.cursor.as_deref().map(Cursor::decode).transpose()?;
+        let sort_fields = req.sort.unwrap_or_default();
+        let view = MetadataView::new(Arc::clone(&ctx.config), req.password);
+        let result = ctx.secret_manager().find(
+            filter.map(|filter| {
+                let view = &view;
+
+                move |meta: &Metadata| {
+                    view.with(meta, |meta| filter.matches(meta))
+                }
+            }),
+            (!sort_fields.is_empty()).then_some(
+                |a: &Metadata, b: &Metadata| {
+                    compare_keys(
+                        &sort_keys(&view, a, &sort_fields),
+                        &sort_keys(&view, b, &sort_fields),
+                        &sort_fields,
+                    )
+                },
+            ),
+            cursor.map(|cursor| {
+                let sort_fields = &sort_fields;
+                let view = &view;
+
+                move |path: &Path, meta: &Metadata| {
+                    compare_keys(
+                        &sort_keys(view, meta, sort_fields),
+                        &cursor.keys,
+                        sort_fields,
+                    )
+                    .then_with(|| path.cmp(&cursor.path))
+                        == Ordering::Greater
                 }
+            }),
+            req.offset.map(|x| x as usize),
+            req.limit.map(|x| x as usize),
+            &ctx.cancel,
+        )?;
+
+        let consumed = req.offset.unwrap_or(0) as usize + result.hits.len();
+        let next_cursor = match result.hits.last() {
+            Some((path, metadata))
+                if req.limit.is_some() && consumed < result.remaining =>
+            {
+                Some(
+                    Cursor {
+                        keys: sort_keys(&view, metadata, &sort_fields),
+                        path: path.clone(),
+                    }
+                    .encode()?,
+                )
             }
+            _ => None,
+        };
+
+        let Some(fields) = req.fields else {
+            let paths = result.hits.into_iter().map(|(path, _)| path).collect();
+
+            // Offset/limit clients expect a bare list, so the page object
+            // is only returned to callers that page by cursor.
+            view.check()?;
+
+            return Ok(if req.paged || req.cursor.is_some() {
+                FindResponse::Page {
+                    total: result.total,
+                    paths,
+                    next_cursor,
+                }
+            } else {
+                FindResponse::Paths(paths)
+            });
+        };
+
+        let hits = result
+            .hits
+            .iter()
+            .map(|(path, metadata)| {
+                view.with(metadata, |metadata| project(path, metadata, &fields))
+            })
+            .collect();
+
+        view.check()?;
+
+        Ok(FindResponse::Hits {
+            total: result.total,
+            hits,
+            next_cursor,
+        })
+    }
+}
+
+fn project(
+    path: &Path,
+    metadata: &Metadata,
+    fields: &[String],
+) -> Map<String, Value> {
+    let mut hit = Map::new();
+
+    hit.insert("path".to_string(), Value::from(path.to_string_lossy()));
+
+    for field in fields.iter().filter(|f| f.as_str() != "path") {
+        if let Some(value) = metadata.get_field(field).ok().flatten() {
+            hit.insert(field.clone(), value);
         }
     }
+
+    hit
 }
 
-pub fn handler(
-    params: Params,
-    ctx: &Arc<Config>,
-    _ext: &Extensions,
-) -> Result<Vec<PathBuf>, ErrorObject<'static>> {
-    let req: QueryRequest = params.parse().map_err(|e| {
-        error!("Failed to parse query parameters: {}", e);
-
-        ErrorObject::owned(
-            jsonrpsee::types::error::INVALID_PARAMS_CODE,
-            "Invalid query parameters",
-            Some(e.to_string()),
-        )
-    })?;
-
-    let result = Secrets {
-        config: Arc::clone(ctx),
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::tests::{context, sealed_vault, write_metadata};
+    use serde_json::json;
+
+    fn request(value: Value) -> QueryRequest {
+        serde_json::from_value(value).unwrap()
     }
-    .find(
-        req.filter
-            .map(|filter| move |meta: &Metadata| filter.matches(meta)),
-        req.sort.map(|sort_fields| {
-            move |a: &Metadata, b: &Metadata| {
-                let mut ord = Ordering::Equal;
-
-                for field in &sort_fields {
-                    let a_val = a
-                        .get_field(&field.field)
-                        .ok()
-                        .flatten()
-                        .unwrap_or(Value::Null);
-                    let b_val = b
-                        .get_field(&field.field)
-                        .ok()
-                        .flatten()
-                        .unwrap_or(Value::Null);
-
-                    if let Some(cmp) = compare_values(&a_val, &b_val) {
-                        ord = match field.direction {
-                            SortDirection::Asc => cmp,
-                            SortDirection::Desc => cmp.reverse(),
-                        };
-
-                        if ord != Ordering::Equal {
-                            break;
-                        }
-                    }
-                }
-                ord
+
+    fn find(ctx: &Context, value: Value) -> Value {
+        serde_json::to_value(Find::handle(request(value), ctx).unwrap())
+            .unwrap()
+    }
+
+    fn vault(dir: &Path) -> Context {
+        let ctx = context(dir);
+
+        for path in ["a", "b", "c"] {
+            write_metadata(
+                &ctx,
+                &Metadata {
+                    path: PathBuf::from(path),
+                    ..Metadata::default()
+                },
+            );
+        }
+
+        ctx
+    }
+
+    #[test]
+    fn offset_and_limit_still_return_a_bare_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = vault(dir.path());
+
+        assert_eq!(find(&ctx, json!({})), json!(["a", "b", "c"]));
+        assert_eq!(
+            find(&ctx, json!({ "offset": 1, "limit": 1 })),
+            json!(["b"])
+        );
+    }
+
+    #[test]
+    fn paged_requests_continue_from_the_cursor() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = vault(dir.path());
+        let first = find(&ctx, json!({ "paged": true, "limit": 2 }));
+
+        assert_eq!(first["total"], json!(3));
+        assert_eq!(first["paths"], json!(["a", "b"]));
+
+        let second =
+            find(&ctx, json!({ "limit": 2, "cursor": first["next_cursor"] }));
+
+        assert_eq!(second["total"], json!(3));
+        assert_eq!(second["paths"], json!(["c"]));
+        assert_eq!(second.get("next_cursor"), None);
+    }
+
+    #[test]
+    fn entries_the_password_cannot_open_do_not_match_sealed_fields() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = sealed_vault(dir.path());
+
+        assert_eq!(find(&ctx, json!({ "query": "owner:alice" })), json!([]));
+        assert_eq!(
+            find(
+                &ctx,
+                json!({ "query": "owner:*", "password": "alice-password" })
+            ),
+            json!(["a", "c"])
+        );
+        assert_eq!(
+            find(
+                &ctx,
+                json!({
+                    "fields": ["owner"],
+                    "password": "bob-password",
+                })
+            ),
+            json!({
+                "total": 3,
+                "hits": [
+                    { "path": "a" },
+                    { "path": "b", "owner": "bob" },
+                    { "path": "c", "owner": "carol" },
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn a_password_that_opens_nothing_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = sealed_vault(dir.path());
+        let result = Find::handle(
+            request(json!({ "query": "owner:alice", "password": "wrong" })),
+            &ctx,
+        );
+
+        assert!(matches!(result, Err(Error::InvalidPassword)));
+    }
+
+    #[test]
+    fn sealed_fields_sort_and_page_once_unlocked() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = sealed_vault(dir.path());
+        let page = |cursor: Option<&str>| {
+            find(
+                &ctx,
+                json!({
+                    "sort": [{ "field": "owner", "direction": "desc" }],
+                    "paged": true,
+                    "limit": 1,
+                    "cursor": cursor,
+                    "password": "alice-password",
+                }),
+            )
+        };
+        let mut paths = Vec::new();
+        let mut cursor = None;
+
+        loop {
+            let response = page(cursor.as_deref());
+
+            paths.extend(response["paths"].as_array().unwrap().clone());
+
+            match response["next_cursor"].as_str() {
+                Some(next) => cursor = Some(next.to_string()),
+                None => break,
             }
-        }),
-        req.offset.map(|x| x as usize),
-        req.limit.map(|x| x as usize),
-    );
-
-    match result {
-        Ok(secrets) => Ok(secrets),
-        Err(e) => Err(ErrorObject::owned(
-            jsonrpsee::types::error::INTERNAL_ERROR_CODE,
-            "Unexpected find error",
-            Some(e.to_string()),
-        )),
+        }
+
+        // carol, alice, then bob's entry whose owner stays sealed.
+        assert_eq!(paths, [json!("c"), json!("a"), json!("b")]);
     }
 }
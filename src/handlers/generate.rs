@@ -0,0 +1,63 @@
+use super::{Access, Context, Handler};
+use passd::{
+    Error, models::generator::GenerateOptions, utils::memory::SecretString,
+};
+use serde::Serialize;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct GenerateResponse {
+    value: SecretString,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum WriteResponse {
+    Message(String),
+    Generated {
+        message: String,
+        content: SecretString,
+    },
+}
+
+pub struct Generate;
+
+impl Handler for Generate {
+    type Params = GenerateOptions;
+    type Response = GenerateResponse;
+    const ACCESS: Access = Access::Shared;
+
+    fn handle(
+        params: GenerateOptions,
+        _ctx: &Context,
+    ) -> passd::Result<GenerateResponse> {
+        Ok(GenerateResponse {
+            value: params.generate()?,
+        })
+    }
+}
+
+pub fn resolve_content(
+    content: Option<SecretString>,
+    generate: Option<&GenerateOptions>,
+) -> passd::Result<Option<SecretString>> {
+    match (content, generate) {
+        (Some(_), Some(_)) => Err(Error::InvalidParams(
+            "Only one of content or generate may be given".to_string(),
+        )),
+        (None, Some(options)) => options.generate().map(Some),
+        (content, None) => Ok(content),
+    }
+}
+
+impl WriteResponse {
+    pub fn new(
+        message: String,
+        content: Option<SecretString>,
+        reveal: bool,
+    ) -> Self {
+        match content {
+            Some(content) if reveal => Self::Generated { message, content },
+            _ => Self::Message(message),
+        }
+    }
+}
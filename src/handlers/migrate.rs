@@ -0,0 +1,24 @@
+use super::{Access, Context, Handler};
+use passd::models::secret_manager::MigrationReport;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct MigrateParams {
+    #[serde(default)]
+    dry_run: bool,
+}
+
+pub struct Migrate;
+
+impl Handler for Migrate {
+    type Params = MigrateParams;
+    type Response = MigrationReport;
+    const ACCESS: Access = Access::Exclusive;
+
+    fn handle(
+        params: MigrateParams,
+        ctx: &Context,
+    ) -> passd::Result<MigrationReport> {
+        ctx.secret_manager().migrate(params.dry_run, &ctx.cancel)
+    }
+}
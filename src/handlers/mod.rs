@@ -1,40 +1,490 @@
 use crate::Config;
 use anyhow::Result;
-use jsonrpsee::RpcModule;
-use std::sync::Arc;
+use jsonrpsee::{
+    RpcModule,
+    types::{Extensions, Params},
+};
+use log::{error, info, warn};
+use passd::{
+    Error,
+    models::{
+        index::SharedIndex, secret::Secret, secret_manager::SecretManager,
+        watcher::VaultWatcher,
+    },
+    utils::cancel::CancellationToken,
+};
+use serde::{Serialize, de::DeserializeOwned};
+use std::{path::PathBuf, sync::Arc};
+use tokio::{sync::RwLock, task};
 
+pub mod aggregate;
+pub mod audit;
 pub mod clone_to;
 pub mod copy_to;
 pub mod create;
 pub mod delete;
 pub mod diagnose;
+pub mod expiring;
 pub mod find;
+pub mod generate;
+pub mod migrate;
 pub mod move_to;
+pub mod otp_import;
+pub mod query_delete;
+pub mod query_list;
+pub mod query_run;
+pub mod query_save;
 pub mod read_content;
 pub mod read_metadata;
+pub mod reindex;
+pub mod search;
+pub mod template_list;
+pub mod totp;
+pub mod tree;
 pub mod update;
 
+pub struct State {
+    pub config: Arc<Config>,
+    index: Arc<SharedIndex>,
+    lock: Arc<RwLock<()>>,
+    watcher: Option<Arc<VaultWatcher>>,
+}
+
+impl State {
+    pub fn new(config: Arc<Config>) -> Self {
+        let index = Arc::new(SharedIndex::new());
+        let lock = Arc::new(RwLock::new(()));
+        let watcher = match VaultWatcher::start(
+            Arc::clone(&config),
+            Arc::clone(&index),
+            Arc::clone(&lock),
+        ) {
+            Ok(watcher) => Some(Arc::new(watcher)),
+            Err(e) => {
+                warn!("Failed to start vault watcher: {}", e);
+
+                None
+            }
+        };
+
+        Self {
+            config,
+            index,
+            lock,
+            watcher,
+        }
+    }
+}
+
+pub struct Context {
+    pub config: Arc<Config>,
+    pub index: Arc<SharedIndex>,
+    pub cancel: CancellationToken,
+    pub watcher: Option<Arc<VaultWatcher>>,
+}
+
+/// How a handler shares the vault with requests running at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Only reads the vault and may run alongside other shared handlers.
+    Shared,
+    /// Changes the vault and waits until it is the only handler running.
+    Exclusive,
+}
+
+impl Context {
+    pub fn secret(&self, relative_path: impl Into<PathBuf>) -> Secret {
+        Secret::new(
+            relative_path.into(),
+            Arc::clone(&self.config),
+            Arc::clone(&self.index),
+        )
+    }
+
+    pub fn secret_manager(&self) -> SecretManager {
+        SecretManager::new(Arc::clone(&self.config), Arc::clone(&self.index))
+    }
+}
+
+pub trait Handler {
+    type Params: DeserializeOwned;
+    type Response: Serialize + Clone + Send + 'static;
+    const ACCESS: Access;
+
+    fn handle(
+        params: Self::Params,
+        ctx: &Context,
+    ) -> passd::Result<Self::Response>;
+}
+
+fn dispatch<H: Handler>(
+    method: &'static str,
+    params: Params,
+    state: &State,
+    cancel: CancellationToken,
+) -> passd::Result<H::Response> {
+    let params: H::Params =
+        serde_json::from_str(params.as_str().unwrap_or("{}")).map_err(|e| {
+            error!("Failed to parse '{}' parameters: {}", method, e);
+
+            Error::InvalidParams(e.to_string())
+        })?;
+
+    let ctx = Context {
+        config: Arc::clone(&state.config),
+        index: Arc::clone(&state.index),
+        cancel,
+        watcher: state.watcher.clone(),
+    };
+
+    ctx.cancel.check()?;
+
+    match H::handle(params, &ctx) {
+        Ok(response) => {
+            info!("Successfully handled '{}'", method);
+
+            Ok(response)
+        }
+        Err(e) => {
+            error!("Failed to handle '{}': {}", method, e);
+
+            Err(e)
+        }
+    }
+}
+
+async fn lock_vault(lock: &Arc<RwLock<()>>, access: Access) -> Box<dyn Send> {
+    match access {
+        Access::Shared => Box::new(Arc::clone(lock).read_owned().await),
+        Access::Exclusive => Box::new(Arc::clone(lock).write_owned().await),
+    }
+}
+
+async fn spawn_dispatch<H: Handler>(
+    method: &'static str,
+    params: Params<'static>,
+    state: Arc<State>,
+    extensions: Extensions,
+) -> passd::Result<H::Response> {
+    // WebSocket calls outlive their connection, so the server hands every
+    // call a token that is cancelled when the client goes away.
+    let cancel = extensions
+        .get::<CancellationToken>()
+        .map(CancellationToken::child_token)
+        .unwrap_or_default();
+    let _cancel_on_drop = cancel.drop_guard();
+
+    cancel.check()?;
+
+    let guard = tokio::select! {
+        guard = lock_vault(&state.lock, H::ACCESS) => guard,
+        _ = cancel.cancelled() => {
+            warn!("Abandoned '{}' while waiting for the vault", method);
+
+            return Err(Error::Cancelled);
+        }
+    };
+
+    task::spawn_blocking(move || {
+        let _guard = guard;
+
+        dispatch::<H>(method, params, &state, cancel)
+    })
+    .await
+    .unwrap_or_else(|e| {
+        error!("Handler '{}' panicked: {}", method, e);
+
+        Err(Error::Internal(format!("Handler '{method}' panicked")))
+    })
+}
+
 macro_rules! register {
     ($module:ident, {
-        $($name:literal => $handler:path),* $(,)?
+        $($name:literal => $handler:ty),* $(,)?
     }) => {
-        $($module.register_method($name, $handler)?;)*
+        $($module.register_async_method($name, |params, state, extensions| {
+            spawn_dispatch::<$handler>($name, params, state, extensions)
+        })?;)*
     };
 }
 
-pub fn register_handlers(module: &mut RpcModule<Arc<Config>>) -> Result<()> {
+pub fn register_handlers(module: &mut RpcModule<State>) -> Result<()> {
     register!(module, {
-        "create"        => create::handler,
-        "update"        => update::handler,
-        "delete"        => delete::handler,
-        "read_content"  => read_content::handler,
-        "read_metadata" => read_metadata::handler,
-        "move"          => move_to::handler,
-        "copy"          => copy_to::handler,
-        "clone"         => clone_to::handler,
-        "find"          => find::handler,
-        "diagnose"      => diagnose::handler,
+        "create"        => create::Create,
+        "update"        => update::Update,
+        "delete"        => delete::Delete,
+        "read_content"  => read_content::ReadContent,
+        "read_metadata" => read_metadata::ReadMetadata,
+        "move"          => move_to::MoveTo,
+        "copy"          => copy_to::CopyTo,
+        "clone"         => clone_to::CloneTo,
+        "find"          => find::Find,
+        "tree"          => tree::Tree,
+        "aggregate"     => aggregate::Aggregate,
+        "search"        => search::Search,
+        "query_save"    => query_save::QuerySave,
+        "query_list"    => query_list::QueryList,
+        "query_run"     => query_run::QueryRun,
+        "query_delete"  => query_delete::QueryDelete,
+        "template_list" => template_list::TemplateList,
+        "expiring"      => expiring::Expiring,
+        "generate"      => generate::Generate,
+        "totp"          => totp::Totp,
+        "otp_import"    => otp_import::OtpImport,
+        "diagnose"      => diagnose::Diagnose,
+        "audit"         => audit::Audit,
+        "reindex"       => reindex::Reindex,
+        "migrate"       => migrate::Migrate,
     });
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::serve;
+    use chrono::{Duration as TimeDelta, Utc};
+    use jsonrpsee::server::stop_channel;
+    use passd::models::metadata::{BaseMetadata, Metadata};
+    use sequoia_openpgp::{cert::CertBuilder, serialize::Serialize as _};
+    use serde::Deserialize;
+    use soketto::{
+        connection::{Receiver, Sender},
+        handshake::{Client, ServerResponse},
+    };
+    use std::{
+        collections::HashMap,
+        fs,
+        net::SocketAddr,
+        path::Path,
+        sync::{
+            Mutex,
+            mpsc::{self, RecvTimeoutError},
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+    use tokio::{
+        net::{TcpListener, TcpStream},
+        time,
+    };
+    use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub(super) fn config(dir: &Path) -> Config {
+        Config {
+            base_dir: dir.to_path_buf(),
+            secrets_dir: dir.join("secrets"),
+            metadata_dir: dir.join(".metadata"),
+            keys_dir: dir.join(".keys"),
+            ..Config::default()
+        }
+    }
+
+    /// A context over the vault in `dir` that runs without a watcher.
+    pub(super) fn context(dir: &Path) -> Context {
+        Context {
+            config: Arc::new(config(dir)),
+            index: Arc::new(SharedIndex::new()),
+            cancel: CancellationToken::new(),
+            watcher: None,
+        }
+    }
+
+    /// Stores metadata for a secret without its encrypted content, which is
+    /// all the read-only handlers look at.
+    pub(super) fn write_metadata(ctx: &Context, metadata: &Metadata) {
+        let path = ctx
+            .config
+            .metadata_dir
+            .join(&metadata.path)
+            .with_extension("meta.toml");
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, toml::to_string_pretty(metadata).unwrap()).unwrap();
+    }
+
+    /// Stores a key protected by `password` in the vault's key directory and
+    /// returns its fingerprint. Keys are generated once per name and shared
+    /// between tests, since generating them is slow.
+    pub(super) fn key(ctx: &Context, name: &str, password: &str) -> String {
+        static KEYS: Mutex<Vec<(String, Vec<u8>, String)>> =
+            Mutex::new(Vec::new());
+
+        let mut keys = KEYS.lock().unwrap();
+
+        if !keys.iter().any(|(known, _, _)| known == name) {
+            let (cert, _) = CertBuilder::general_purpose(Some(format!(
+                "{name}@example.com"
+            )))
+            .set_password(Some(password.into()))
+            .generate()
+            .unwrap();
+            let mut armored = Vec::new();
+
+            cert.as_tsk().armored().serialize(&mut armored).unwrap();
+            keys.push((name.to_string(), armored, cert.fingerprint().to_hex()));
+        }
+
+        let (_, armored, fingerprint) =
+            keys.iter().find(|(known, _, _)| known == name).unwrap();
+
+        fs::create_dir_all(&ctx.config.keys_dir).unwrap();
+        fs::write(ctx.config.keys_dir.join(name), armored).unwrap();
+
+        fingerprint.clone()
+    }
+
+    /// A vault with `a` sealed to alice, `b` sealed to bob and `c` left in
+    /// plaintext. Each records its `owner` and expires tomorrow.
+    pub(super) fn sealed_vault(dir: &Path) -> Context {
+        let ctx = context(dir);
+        let alice = key(&ctx, "alice", "alice-password");
+        let bob = key(&ctx, "bob", "bob-password");
+
+        for (path, owner, fingerprint) in [
+            ("a", "alice", &alice),
+            ("b", "bob", &bob),
+            ("c", "carol", &bob),
+        ] {
+            let metadata = BaseMetadata {
+                expires_at: Some(Utc::now() + TimeDelta::days(1)),
+                extra: Some(HashMap::from([(
+                    "owner".to_string(),
+                    toml::Value::from(owner),
+                )])),
+                ..BaseMetadata::default()
+            };
+            let sealed = match path {
+                "c" => Vec::new(),
+                _ => vec!["owner".to_string(), "expires_at".to_string()],
+            };
+
+            ctx.secret(path)
+                .create("hunter2", &metadata, &sealed, &[fingerprint])
+                .unwrap();
+        }
+
+        ctx
+    }
+
+    static EVENTS: Mutex<Option<mpsc::Sender<&'static str>>> = Mutex::new(None);
+
+    fn record(event: &'static str) {
+        if let Some(events) = EVENTS.lock().unwrap().as_ref() {
+            let _ = events.send(event);
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct ParkParams {}
+
+    /// Holds the vault exclusively until its request is cancelled.
+    struct Park;
+
+    impl Handler for Park {
+        type Params = ParkParams;
+        type Response = ();
+        const ACCESS: Access = Access::Exclusive;
+
+        fn handle(_params: ParkParams, ctx: &Context) -> passd::Result<()> {
+            record("started");
+
+            while !ctx.cancel.is_cancelled() {
+                thread::sleep(Duration::from_millis(10));
+            }
+
+            record("cancelled");
+
+            Err(Error::Cancelled)
+        }
+    }
+
+    type Socket = Compat<TcpStream>;
+
+    async fn eventually(condition: impl Fn() -> bool) -> bool {
+        let deadline = Instant::now() + TIMEOUT;
+
+        while Instant::now() < deadline {
+            if condition() {
+                return true;
+            }
+
+            time::sleep(Duration::from_millis(10)).await;
+        }
+
+        false
+    }
+
+    async fn connect(addr: SocketAddr) -> (Sender<Socket>, Receiver<Socket>) {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let host = addr.to_string();
+        let mut client = Client::new(stream.compat(), &host, "/");
+
+        assert!(matches!(
+            client.handshake().await.unwrap(),
+            ServerResponse::Accepted { .. }
+        ));
+
+        client.into_builder().finish()
+    }
+
+    async fn park(sender: &mut Sender<Socket>) {
+        sender
+            .send_text(
+                r#"{"jsonrpc":"2.0","id":1,"method":"park","params":{}}"#,
+            )
+            .await
+            .unwrap();
+        sender.flush().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn closing_websocket_cancels_running_and_queued_calls() -> Result<()>
+    {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+        let (events, received) = mpsc::channel();
+
+        *EVENTS.lock().unwrap() = Some(events);
+
+        let state = State::new(Arc::new(config));
+        let lock = Arc::clone(&state.lock);
+        let mut module = RpcModule::new(state);
+
+        register!(module, { "park" => Park });
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (stop_handle, _server_handle) = stop_channel();
+
+        tokio::spawn(serve(listener, module, stop_handle));
+
+        let (mut running, _running_rx) = connect(addr).await;
+
+        park(&mut running).await;
+        assert_eq!(received.recv_timeout(TIMEOUT), Ok("started"));
+
+        // A call waiting for the vault holds a reference to the lock, so the
+        // count shows it queueing and then giving up once its client leaves.
+        let holders = Arc::strong_count(&lock);
+        let (mut queued, _queued_rx) = connect(addr).await;
+
+        park(&mut queued).await;
+        assert!(eventually(|| Arc::strong_count(&lock) == holders + 1).await);
+
+        queued.close().await.unwrap();
+        assert!(eventually(|| Arc::strong_count(&lock) == holders).await);
+
+        running.close().await.unwrap();
+        assert_eq!(received.recv_timeout(TIMEOUT), Ok("cancelled"));
+        assert_eq!(
+            received.recv_timeout(Duration::from_millis(500)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        Ok(())
+    }
+}
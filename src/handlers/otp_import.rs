@@ -0,0 +1,44 @@
+use super::{Access, Context, Handler};
+use passd::{
+    models::{metadata::BaseMetadata, otp::OtpSeed},
+    utils::memory::SecretString,
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct OtpImportParams {
+    path: String,
+    uri: SecretString,
+    metadata: BaseMetadata,
+    #[serde(default)]
+    encrypt_fields: Vec<String>,
+    recipients: Vec<String>,
+}
+
+pub struct OtpImport;
+
+impl Handler for OtpImport {
+    type Params = OtpImportParams;
+    type Response = String;
+    const ACCESS: Access = Access::Exclusive;
+
+    fn handle(params: OtpImportParams, ctx: &Context) -> passd::Result<String> {
+        let seed = OtpSeed::parse_uri(&params.uri)?;
+        let recipients: Vec<&str> =
+            params.recipients.iter().map(String::as_str).collect();
+        let metadata = seed.describe(params.metadata);
+        let metadata = match ctx.config.template_for(&metadata) {
+            Some(template) => metadata.with_template(template),
+            None => metadata,
+        };
+
+        ctx.secret(params.path.clone()).create(
+            params.uri.trim(),
+            &metadata,
+            &params.encrypt_fields,
+            &recipients,
+        )?;
+
+        Ok(format!("Successfully imported OTP secret {}", params.path))
+    }
+}
@@ -0,0 +1,28 @@
+use super::{Access, Context, Handler};
+use passd::models::saved_query::SavedQueries;
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct QueryDeleteParams {
+    pub name: String,
+}
+
+pub struct QueryDelete;
+
+impl Handler for QueryDelete {
+    type Params = QueryDeleteParams;
+    type Response = String;
+    const ACCESS: Access = Access::Exclusive;
+
+    fn handle(
+        params: QueryDeleteParams,
+        ctx: &Context,
+    ) -> passd::Result<String> {
+        let mut queries = SavedQueries::load(&ctx.config)?;
+
+        queries.remove(&params.name)?;
+        queries.save(&ctx.config)?;
+
+        Ok(format!("Successfully deleted query '{}'", params.name))
+    }
+}
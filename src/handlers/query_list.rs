@@ -0,0 +1,24 @@
+use super::{Access, Context, Handler};
+use passd::models::saved_query::{SavedQueries, SavedQuery};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct QueryListParams {}
+
+pub struct QueryList;
+
+impl Handler for QueryList {
+    type Params = QueryListParams;
+    type Response = Vec<SavedQuery>;
+    const ACCESS: Access = Access::Shared;
+
+    fn handle(
+        _params: QueryListParams,
+        ctx: &Context,
+    ) -> passd::Result<Vec<SavedQuery>> {
+        Ok(SavedQueries::load(&ctx.config)?
+            .queries
+            .into_values()
+            .collect())
+    }
+}
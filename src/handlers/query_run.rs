@@ -0,0 +1,52 @@
+use super::{
+    Access, Context, Handler,
+    find::{Find, FindResponse, QueryRequest},
+};
+use passd::{
+    Error, models::saved_query::SavedQueries, utils::memory::SecretString,
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct QueryRunParams {
+    pub name: String,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    pub cursor: Option<String>,
+    pub fields: Option<Vec<String>>,
+    #[serde(default)]
+    pub paged: bool,
+    pub password: Option<SecretString>,
+}
+
+pub struct QueryRun;
+
+impl Handler for QueryRun {
+    type Params = QueryRunParams;
+    type Response = FindResponse;
+    const ACCESS: Access = Access::Shared;
+
+    fn handle(
+        params: QueryRunParams,
+        ctx: &Context,
+    ) -> passd::Result<FindResponse> {
+        let queries = SavedQueries::load(&ctx.config)?;
+        let saved = queries.get(&params.name)?;
+        let mut request: QueryRequest =
+            serde_json::from_value(saved.request.clone()).map_err(|e| {
+                Error::Internal(format!(
+                    "Saved query '{}' is invalid: {e}",
+                    params.name
+                ))
+            })?;
+
+        request.limit = params.limit.or(request.limit);
+        request.offset = params.offset.or(request.offset);
+        request.cursor = params.cursor.or(request.cursor);
+        request.fields = params.fields.or(request.fields);
+        request.paged |= params.paged;
+        request.password = params.password;
+
+        Find::handle(request, ctx)
+    }
+}
@@ -0,0 +1,30 @@
+use super::{Access, Context, Handler, find::QueryRequest};
+use passd::{Error, models::saved_query::SavedQueries};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct QuerySaveParams {
+    pub name: String,
+    pub description: Option<String>,
+    pub request: QueryRequest,
+}
+
+pub struct QuerySave;
+
+impl Handler for QuerySave {
+    type Params = QuerySaveParams;
+    type Response = String;
+    const ACCESS: Access = Access::Exclusive;
+
+    fn handle(params: QuerySaveParams, ctx: &Context) -> passd::Result<String> {
+        let request = serde_json::to_value(&params.request).map_err(|e| {
+            Error::Internal(format!("Failed to serialize query: {e}"))
+        })?;
+        let mut queries = SavedQueries::load(&ctx.config)?;
+
+        queries.insert(&params.name, params.description, request)?;
+        queries.save(&ctx.config)?;
+
+        Ok(format!("Successfully saved query '{}'", params.name))
+    }
+}
@@ -0,0 +1,27 @@
+use super::{Access, Context, Handler};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct ReindexParams {}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ReindexResponse {
+    indexed: usize,
+}
+
+pub struct Reindex;
+
+impl Handler for Reindex {
+    type Params = ReindexParams;
+    type Response = ReindexResponse;
+    const ACCESS: Access = Access::Exclusive;
+
+    fn handle(
+        _params: ReindexParams,
+        ctx: &Context,
+    ) -> passd::Result<ReindexResponse> {
+        let indexed = ctx.secret_manager().reindex(&ctx.cancel)?;
+
+        Ok(ReindexResponse { indexed })
+    }
+}
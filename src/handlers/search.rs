@@ -0,0 +1,109 @@
+use super::{Access, Context, Handler};
+use passd::models::{
+    filter::{Filter, combine_filter},
+    metadata::Metadata,
+    search::SearchHit,
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct SearchParams {
+    pub text: String,
+    pub filter: Option<Filter>,
+    pub query: Option<String>,
+    #[serde(default)]
+    pub fuzzy: bool,
+    pub limit: Option<u32>,
+}
+
+pub struct Search;
+
+impl Handler for Search {
+    type Params = SearchParams;
+    type Response = Vec<SearchHit>;
+    const ACCESS: Access = Access::Shared;
+
+    fn handle(
+        params: SearchParams,
+        ctx: &Context,
+    ) -> passd::Result<Vec<SearchHit>> {
+        let filter = combine_filter(params.filter, params.query)?;
+
+        ctx.secret_manager().search(
+            &params.text,
+            filter.map(|filter| move |meta: &Metadata| filter.matches(meta)),
+            params.fuzzy,
+            params.limit.map(|x| x as usize),
+            &ctx.cancel,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::tests::{context, write_metadata};
+    use passd::models::metadata::BaseMetadata;
+    use serde_json::{Value, json};
+    use std::path::PathBuf;
+
+    fn search(ctx: &Context, params: Value) -> Vec<String> {
+        let params = serde_json::from_value(params).unwrap();
+
+        Search::handle(params, ctx)
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.path.display().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn hits_are_ranked_by_relevance() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = context(dir.path());
+
+        for (path, category, tags, description) in [
+            ("web/mirror", "home", vec![], "Mirror of our GitHub repos"),
+            ("mail/github", "work", vec![], ""),
+            ("web/code", "work", vec!["github"], ""),
+            ("web/bank", "home", vec![], "Online banking"),
+        ] {
+            write_metadata(
+                &ctx,
+                &Metadata {
+                    path: PathBuf::from(path),
+                    template: BaseMetadata {
+                        category: Some(category.to_string()),
+                        tags: Some(
+                            tags.into_iter().map(str::to_string).collect(),
+                        ),
+                        description: Some(description.to_string()),
+                        ..BaseMetadata::default()
+                    },
+                    ..Metadata::default()
+                },
+            );
+        }
+
+        assert_eq!(
+            search(&ctx, json!({ "text": "github" })),
+            ["mail/github", "web/code", "web/mirror"]
+        );
+        assert_eq!(
+            search(&ctx, json!({ "text": "github", "limit": 2 })),
+            ["mail/github", "web/code"]
+        );
+        assert_eq!(
+            search(&ctx, json!({ "text": "github", "query": "category:home" })),
+            ["web/mirror"]
+        );
+        assert_eq!(
+            search(&ctx, json!({ "text": "gihtub" })),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            search(&ctx, json!({ "text": "githubb", "fuzzy": true })),
+            ["mail/github", "web/code", "web/mirror"]
+        );
+    }
+}
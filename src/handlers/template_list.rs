@@ -0,0 +1,36 @@
+use super::{Access, Context, Handler};
+use passd::models::metadata::BaseMetadata;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Debug, Deserialize)]
+pub struct TemplateListParams {}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TemplateListResponse {
+    default: Option<BaseMetadata>,
+    templates: BTreeMap<String, BaseMetadata>,
+}
+
+pub struct TemplateList;
+
+impl Handler for TemplateList {
+    type Params = TemplateListParams;
+    type Response = TemplateListResponse;
+    const ACCESS: Access = Access::Shared;
+
+    fn handle(
+        _params: TemplateListParams,
+        ctx: &Context,
+    ) -> passd::Result<TemplateListResponse> {
+        Ok(TemplateListResponse {
+            default: ctx.config.metadata_template.clone(),
+            templates: ctx
+                .config
+                .templates
+                .iter()
+                .map(|(name, template)| (name.clone(), template.clone()))
+                .collect(),
+        })
+    }
+}
@@ -0,0 +1,88 @@
+use super::{Access, Context, Handler};
+use chrono::Utc;
+use passd::{
+    models::otp::{OtpCode, OtpKind, OtpSeed},
+    utils::memory::SecretString,
+};
+use serde::Deserialize;
+
+#[derive(Debug, Deserialize)]
+pub struct TotpParams {
+    path: String,
+    password: Option<SecretString>,
+}
+
+pub struct Totp;
+
+impl Handler for Totp {
+    type Params = TotpParams;
+    type Response = OtpCode;
+    const ACCESS: Access = Access::Exclusive;
+
+    fn handle(params: TotpParams, ctx: &Context) -> passd::Result<OtpCode> {
+        let password = params.password.unwrap_or_default();
+        let secret = ctx.secret(params.path);
+        let content = secret.plaintext_content(&password)?;
+        let metadata = secret.unlocked_metadata(&password)?;
+        let seed = OtpSeed::from_secret(&content, &metadata)?;
+        let code = seed.code(Utc::now())?;
+
+        if seed.kind == OtpKind::Hotp {
+            secret.set_otp_counter(seed.counter + 1, &password)?;
+        }
+
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::tests::{context, key};
+    use passd::models::metadata::BaseMetadata;
+
+    #[test]
+    fn hotp_codes_advance_without_counting_as_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = context(dir.path());
+        let fingerprint = key(&ctx, "alice", "alice-password");
+        let metadata = BaseMetadata {
+            r#type: Some("hotp".to_string()),
+            ..BaseMetadata::default()
+        };
+
+        // The RFC 4226 test secret, "12345678901234567890" in base32.
+        ctx.secret("otp")
+            .create(
+                "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ",
+                &metadata,
+                &[],
+                &[&fingerprint],
+            )
+            .unwrap();
+
+        let before = ctx.secret("otp").metadata().unwrap();
+        let code = || {
+            let params = serde_json::from_value(serde_json::json!({
+                "path": "otp",
+                "password": "alice-password",
+            }))
+            .unwrap();
+
+            Totp::handle(params, &ctx).unwrap()
+        };
+
+        assert_eq!(&*code().code, "755224");
+        assert_eq!(&*code().code, "287082");
+
+        let after = ctx.secret("otp").metadata().unwrap();
+
+        assert_eq!(after.modifications, before.modifications);
+        assert_eq!(after.updated_at, before.updated_at);
+        assert_eq!(after.rotated_at, before.rotated_at);
+        assert_eq!(
+            after.template.extra.unwrap()["counter"].as_integer(),
+            Some(2)
+        );
+    }
+}
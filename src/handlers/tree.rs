@@ -0,0 +1,250 @@
+use super::{Access, Context, Handler};
+use passd::{
+    models::{
+        filter::{Filter, combine_filter},
+        metadata::Metadata,
+        unlock::MetadataView,
+    },
+    utils::memory::SecretString,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct TreeParams {
+    pub filter: Option<Filter>,
+    pub query: Option<String>,
+    pub root: Option<PathBuf>,
+    pub depth: Option<u32>,
+    pub password: Option<SecretString>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TreeNode {
+    pub name: String,
+    pub count: usize,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub secrets: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub truncated: bool,
+}
+
+#[derive(Default)]
+struct DirectoryBuilder {
+    count: usize,
+    secrets: Vec<String>,
+    children: BTreeMap<String, DirectoryBuilder>,
+    truncated: bool,
+}
+
+impl DirectoryBuilder {
+    fn insert(&mut self, components: &[String], depth: Option<u32>) {
+        self.count += 1;
+
+        match components {
+            [] => {}
+            [name] => self.secrets.push(name.clone()),
+            [directory, rest @ ..] => {
+                if depth == Some(0) {
+                    self.truncated = true;
+                } else {
+                    self.children
+                        .entry(directory.clone())
+                        .or_default()
+                        .insert(rest, depth.map(|d| d - 1));
+                }
+            }
+        }
+    }
+
+    fn build(self, name: String) -> TreeNode {
+        TreeNode {
+            name,
+            count: self.count,
+            secrets: self.secrets,
+            children: self
+                .children
+                .into_iter()
+                .map(|(name, child)| child.build(name))
+                .collect(),
+            truncated: self.truncated,
+        }
+    }
+}
+
+pub struct Tree;
+
+impl Handler for Tree {
+    type Params = TreeParams;
+    type Response = TreeNode;
+    const ACCESS: Access = Access::Shared;
+
+    fn handle(params: TreeParams, ctx: &Context) -> passd::Result<TreeNode> {
+        let root = params.root.unwrap_or_default();
+        let filter = combine_filter(params.filter, params.query)?;
+        let view = MetadataView::new(Arc::clone(&ctx.config), params.password);
+        let result = ctx.secret_manager().find(
+            filter.map(|filter| {
+                let view = &view;
+
+                move |meta: &Metadata| {
+                    view.with(meta, |meta| filter.matches(meta))
+                }
+            }),
+            None::<fn(&Metadata, &Metadata) -> _>,
+            None::<fn(&Path, &Metadata) -> _>,
+            None,
+            None,
+            &ctx.cancel,
+        )?;
+
+        view.check()?;
+
+        let mut tree = DirectoryBuilder::default();
+
+        for (path, _) in &result.hits {
+            let Ok(relative) = path.strip_prefix(&root) else {
+                continue;
+            };
+            let components: Vec<String> = relative
+                .components()
+                .filter_map(|c| match c {
+                    Component::Normal(name) => {
+                        Some(name.to_string_lossy().into_owned())
+                    }
+                    _ => None,
+                })
+                .collect();
+
+            if !components.is_empty() {
+                tree.insert(&components, params.depth);
+            }
+        }
+
+        Ok(tree.build(root.to_string_lossy().into_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::tests::{context, sealed_vault, write_metadata};
+    use passd::models::metadata::BaseMetadata;
+    use serde_json::{Value, json};
+
+    fn tree(ctx: &Context, value: Value) -> Value {
+        let params = serde_json::from_value(value).unwrap();
+
+        serde_json::to_value(Tree::handle(params, ctx).unwrap()).unwrap()
+    }
+
+    fn vault(dir: &Path) -> Context {
+        let ctx = context(dir);
+
+        for (path, category) in [
+            ("mail", "work"),
+            ("web/bank", "home"),
+            ("web/shop/a", "work"),
+            ("web/shop/b", "home"),
+            ("ssh/server/prod/db", "work"),
+        ] {
+            write_metadata(
+                &ctx,
+                &Metadata {
+                    path: PathBuf::from(path),
+                    template: BaseMetadata {
+                        category: Some(category.to_string()),
+                        ..BaseMetadata::default()
+                    },
+                    ..Metadata::default()
+                },
+            );
+        }
+
+        ctx
+    }
+
+    #[test]
+    fn depth_truncates_directories_but_keeps_their_counts() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = vault(dir.path());
+
+        assert_eq!(
+            tree(&ctx, json!({ "depth": 0 })),
+            json!({
+                "name": "",
+                "count": 5,
+                "secrets": ["mail"],
+                "truncated": true,
+            })
+        );
+        assert_eq!(
+            tree(&ctx, json!({ "depth": 1 })),
+            json!({
+                "name": "",
+                "count": 5,
+                "secrets": ["mail"],
+                "children": [
+                    { "name": "ssh", "count": 1, "truncated": true },
+                    {
+                        "name": "web",
+                        "count": 3,
+                        "secrets": ["bank"],
+                        "truncated": true,
+                    },
+                ],
+            })
+        );
+        assert_eq!(tree(&ctx, json!({})), tree(&ctx, json!({ "depth": 4 })));
+    }
+
+    #[test]
+    fn root_and_filter_narrow_the_tree() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = vault(dir.path());
+
+        assert_eq!(
+            tree(&ctx, json!({ "root": "web" })),
+            json!({
+                "name": "web",
+                "count": 3,
+                "secrets": ["bank"],
+                "children": [
+                    { "name": "shop", "count": 2, "secrets": ["a", "b"] },
+                ],
+            })
+        );
+        assert_eq!(
+            tree(&ctx, json!({ "root": "web", "query": "category:work" })),
+            json!({
+                "name": "web",
+                "count": 1,
+                "children": [{ "name": "shop", "count": 1, "secrets": ["a"] }],
+            })
+        );
+    }
+
+    #[test]
+    fn sealed_fields_filter_the_tree_once_unlocked() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = sealed_vault(dir.path());
+
+        assert_eq!(
+            tree(&ctx, json!({ "query": "owner:alice" })),
+            json!({ "name": "", "count": 0 })
+        );
+        assert_eq!(
+            tree(
+                &ctx,
+                json!({ "query": "owner:alice", "password": "alice-password" })
+            ),
+            json!({ "name": "", "count": 1, "secrets": ["a"] })
+        );
+    }
+}
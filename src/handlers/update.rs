@@ -1,59 +1,142 @@
-use crate::Config;
-use jsonrpsee::Extensions;
-use jsonrpsee::types::{ErrorObject, Params};
-use log::{error, info};
-use passd::models::{metadata::BaseMetadata, secret::Secret};
+use super::{
+    Access, Context, Handler,
+    generate::{WriteResponse, resolve_content},
+};
+use passd::{
+    Error,
+    models::{
+        content::PASSWORD_FIELD,
+        generator::GenerateOptions,
+        metadata::{BaseMetadata, MetadataOp},
+    },
+    utils::memory::SecretString,
+};
 use serde::Deserialize;
-use std::sync::Arc;
 
 #[derive(Debug, Deserialize)]
-struct UpdateParams {
+pub struct UpdateParams {
     path: String,
-    content: Option<String>,
+    content: Option<SecretString>,
+    field: Option<String>,
+    generate: Option<GenerateOptions>,
+    #[serde(default)]
+    reveal: bool,
     metadata: Option<BaseMetadata>,
-    public_key: Option<String>,
+    #[serde(default)]
+    metadata_ops: Vec<MetadataOp>,
+    #[serde(default)]
+    encrypt_fields: Vec<String>,
+    recipients: Option<Vec<String>>,
+    password: Option<SecretString>,
 }
 
-pub fn handler(
-    params: Params,
-    ctx: &Arc<Config>,
-    _ext: &Extensions,
-) -> Result<String, ErrorObject<'static>> {
-    let update_params: UpdateParams = params.parse().map_err(|e| {
-        error!("Failed to parse parameters: {}", e);
-
-        ErrorObject::owned(
-            jsonrpsee::types::error::INVALID_PARAMS_CODE,
-            "Invalid parameters",
-            Some(format!("Failed to parse parameters: {}", e)),
-        )
-    })?;
-
-    match (Secret {
-        relative_path: update_params.path.clone().into(),
-        config: Arc::clone(ctx),
-    })
-    .update(
-        update_params.content.as_deref(),
-        update_params.metadata.as_ref(),
-        update_params.public_key.as_deref(),
-    ) {
-        Ok(_) => {
-            info!("Successfully updated secret {}", update_params.path);
-
-            Ok(format!(
-                "Successfully updated secret {}",
-                update_params.path
-            ))
-        }
-        Err(e) => {
-            error!("Failed to update secret {}: {}", update_params.path, e);
-
-            Err(ErrorObject::owned(
-                jsonrpsee::types::error::INTERNAL_ERROR_CODE,
-                format!("Failed to update secret {}", update_params.path),
-                Some(e.to_string()),
-            ))
-        }
+pub struct Update;
+
+impl Handler for Update {
+    type Params = UpdateParams;
+    type Response = WriteResponse;
+    const ACCESS: Access = Access::Exclusive;
+
+    fn handle(
+        params: UpdateParams,
+        ctx: &Context,
+    ) -> passd::Result<WriteResponse> {
+        let generated = params.generate.is_some();
+        let content =
+            resolve_content(params.content, params.generate.as_ref())?;
+        let recipients: Option<Vec<&str>> = params
+            .recipients
+            .as_ref()
+            .map(|r| r.iter().map(String::as_str).collect());
+        let password = params.password.unwrap_or_default();
+        let secret = ctx.secret(params.path.clone());
+        let updated_content = match (&params.field, &content) {
+            (Some(field), Some(value)) => {
+                Some(secret.content_with_field(field, value, &password)?)
+            }
+            (Some(_), None) => {
+                return Err(Error::InvalidParams(
+                    "Updating a content field requires content or generate"
+                        .to_string(),
+                ));
+            }
+            (None, _) => None,
+        };
+
+        let password_changed = params
+            .field
+            .as_deref()
+            .is_none_or(|field| field == PASSWORD_FIELD);
+
+        secret.update(
+            updated_content.as_deref().or(content.as_deref()),
+            password_changed,
+            params.metadata.as_ref(),
+            &params.metadata_ops,
+            &params.encrypt_fields,
+            recipients.as_deref(),
+            &password,
+        )?;
+
+        Ok(WriteResponse::new(
+            format!("Successfully updated secret {}", params.path),
+            content.filter(|_| generated),
+            params.reveal,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::handlers::tests::{context, key};
+    use serde_json::{Value, json};
+
+    #[test]
+    fn only_password_changes_count_as_rotations() {
+        let dir = tempfile::tempdir().unwrap();
+        let ctx = context(dir.path());
+        let fingerprint = key(&ctx, "alice", "alice-password");
+        let update = |mut params: Value| {
+            params["path"] = json!("mail");
+            params["password"] = json!("alice-password");
+            Update::handle(serde_json::from_value(params).unwrap(), &ctx)
+                .unwrap();
+
+            ctx.secret("mail").metadata().unwrap()
+        };
+
+        ctx.secret("mail")
+            .create(
+                "hunter2\nuser: alice",
+                &BaseMetadata::default(),
+                &[],
+                &[&fingerprint],
+            )
+            .unwrap();
+
+        let metadata = update(json!({
+            "metadata_ops": [
+                { "op": "set", "field": "description", "value": "Mail" },
+            ],
+        }));
+
+        assert_eq!(metadata.modifications, 1);
+        assert_eq!(metadata.rotated_at, None);
+
+        let metadata = update(json!({ "field": "user", "content": "bob" }));
+
+        assert_eq!(metadata.modifications, 2);
+        assert_eq!(metadata.rotated_at, None);
+
+        let metadata = update(json!({ "content": "correct-horse" }));
+
+        assert_eq!(metadata.modifications, 3);
+        assert_eq!(metadata.rotated_at, Some(metadata.updated_at));
+
+        let rotated_at = metadata.rotated_at;
+        let metadata = update(json!({ "field": "password", "content": "x" }));
+
+        assert!(metadata.rotated_at > rotated_at);
     }
 }
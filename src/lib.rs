@@ -1,2 +1,5 @@
+pub mod error;
 pub mod models;
 pub mod utils;
+
+pub use error::{Error, Result};
@@ -1,34 +1,45 @@
 use std::{net::SocketAddr, sync::Arc};
 
 use anyhow::{Context, Result};
-use jsonrpsee::{RpcModule, server::ServerBuilder};
-use log::info;
+use jsonrpsee::{RpcModule, server::stop_channel};
+use log::{info, warn};
+use tokio::net::TcpListener;
 
-use passd::{models::config::Config, utils::logger::init_logger};
+use passd::{
+    models::config::Config,
+    utils::{logger::init_logger, memory::disable_core_dumps},
+};
 
 mod handlers;
+mod server;
+
+use handlers::State;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let config =
         Config::load_config().context("Failed to load configuration")?;
 
-    init_logger(&config.log_file, &config.log_level)
+    init_logger(&config.log_file, config.log_level.clone())
         .context("Failed to initialize logger")?;
 
+    if let Err(e) = disable_core_dumps() {
+        warn!("Failed to disable core dumps: {}", e);
+    }
+
     let addr = SocketAddr::new(config.address, config.port);
 
-    let mut module = RpcModule::new(Arc::new(config));
+    let mut module = RpcModule::new(State::new(Arc::new(config)));
 
     handlers::register_handlers(&mut module)
         .context("Failed to register handlers")?;
 
-    let server = ServerBuilder::default()
-        .build(addr)
+    let listener = TcpListener::bind(addr)
         .await
         .context("Failed to build server")?;
+    let (stop_handle, handle) = stop_channel();
 
-    let handle = server.start(module);
+    tokio::spawn(server::serve(listener, module, stop_handle));
 
     info!("Server running on {}", addr);
 
@@ -0,0 +1,510 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use zeroize::Zeroizing;
+
+use crate::{
+    error::Result,
+    models::{
+        generator::WORDLIST,
+        search::char_edit_distance,
+        secret_manager::{DiagnosticResult, DiagnosticStatus, IssueType},
+    },
+    utils::{
+        cancel::CancellationToken, checksum::compute_checksum,
+        memory::SecretString,
+    },
+};
+
+const COMMON_PASSWORDS: &str =
+    include_str!("../../assets/common-passwords.txt");
+
+const MIN_LENGTH: usize = 12;
+const MIN_SIMILAR_LENGTH: usize = 8;
+const MAX_SIMILAR_EDITS: usize = 2;
+// Patterns are only searched in the first characters; anything beyond is
+// counted as random, which keeps long keys and tokens cheap to score.
+const MAX_ANALYZED: usize = 128;
+
+const MIN_WORD_LENGTH: usize = 4;
+const MIN_RUN_LENGTH: usize = 3;
+const KEYBOARD_ROWS: [&str; 4] =
+    ["1234567890-=", "qwertyuiop[]", "asdfghjkl;'", "zxcvbnm,./"];
+const KEYBOARD_KEYS: f64 = 47.0;
+const YEARS: f64 = 200.0;
+const LEET: [(char, char); 9] = [
+    ('4', 'a'),
+    ('@', 'a'),
+    ('3', 'e'),
+    ('1', 'i'),
+    ('!', 'i'),
+    ('0', 'o'),
+    ('$', 's'),
+    ('5', 's'),
+    ('7', 't'),
+];
+
+// Upper bounds in bits for scores 0 to 3; anything stronger scores 4.
+const SCORE_BITS: [f64; 4] = [20.0, 33.0, 50.0, 66.0];
+
+#[derive(Debug, Clone, Copy)]
+pub struct Strength {
+    pub score: u8,
+    pub bits: f64,
+}
+
+pub struct PasswordAuditor {
+    words: HashSet<&'static str>,
+    common: HashMap<&'static str, usize>,
+}
+
+fn class_size(c: char) -> f64 {
+    match c {
+        'a'..='z' | 'A'..='Z' => 26.0,
+        '0'..='9' => 10.0,
+        c if c.is_ascii_punctuation() || c == ' ' => 33.0,
+        _ => 100.0,
+    }
+}
+
+fn charset_size(chars: &[char]) -> f64 {
+    let mut seen = [false; 5];
+
+    for c in chars {
+        let class = match c {
+            'a'..='z' => 0,
+            'A'..='Z' => 1,
+            '0'..='9' => 2,
+            c if c.is_ascii_punctuation() || *c == ' ' => 3,
+            _ => 4,
+        };
+
+        seen[class] = true;
+    }
+
+    [26.0, 26.0, 10.0, 33.0, 100.0]
+        .iter()
+        .zip(seen)
+        .filter(|(_, seen)| *seen)
+        .map(|(size, _)| size)
+        .sum::<f64>()
+        .max(1.0)
+}
+
+fn is_repeat(chars: &[char]) -> bool {
+    chars.windows(2).all(|pair| pair[0] == pair[1])
+}
+
+fn is_sequence(chars: &[char]) -> bool {
+    let step = |pair: &[char]| pair[1] as i64 - pair[0] as i64;
+    let first = step(&chars[..2]);
+
+    first.abs() == 1 && chars.windows(2).all(|pair| step(pair) == first)
+}
+
+fn is_keyboard_run(lower: &[char]) -> bool {
+    let matches = |keys: &mut dyn Iterator<Item = &u8>| {
+        keys.zip(lower).all(|(key, c)| *key as char == *c)
+    };
+
+    KEYBOARD_ROWS.iter().any(|row| {
+        row.as_bytes().windows(lower.len()).any(|keys| {
+            matches(&mut keys.iter()) || matches(&mut keys.iter().rev())
+        })
+    })
+}
+
+fn is_year(chars: &[char]) -> bool {
+    let year = chars
+        .iter()
+        .try_fold(0, |year, c| Some(year * 10 + c.to_digit(10)?));
+
+    chars.len() == 4 && year.is_some_and(|year| (1900..2100).contains(&year))
+}
+
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn unleet(c: char) -> char {
+    LEET.iter()
+        .find(|(leet, _)| *leet == c)
+        .map_or(c, |(_, plain)| *plain)
+}
+
+// Copies `chars` into `scratch` for a lookup by `&str`. The scratch buffer is
+// sized for four bytes per character up front, so it never reallocates and
+// leaves no copy of the password behind.
+fn fill<'a>(scratch: &'a mut String, chars: &[char]) -> &'a str {
+    scratch.clear();
+    scratch.extend(chars);
+
+    scratch
+}
+
+// A password split into characters, lowercased and with leetspeak undone,
+// held in wiped buffers so substrings can be analysed without allocating.
+struct Analyzed {
+    chars: Zeroizing<Vec<char>>,
+    lower: Zeroizing<Vec<char>>,
+    unleet: Zeroizing<Vec<char>>,
+    scratch: Zeroizing<String>,
+}
+
+impl Analyzed {
+    fn new(password: &str) -> Self {
+        let len = password.chars().count();
+        let mut chars = Zeroizing::new(Vec::with_capacity(len));
+        let mut lower = Zeroizing::new(Vec::with_capacity(len));
+        let mut unleeted = Zeroizing::new(Vec::with_capacity(len));
+
+        chars.extend(password.chars());
+        lower.extend(chars.iter().copied().map(lowercase));
+        unleeted.extend(lower.iter().copied().map(unleet));
+
+        Self {
+            chars,
+            lower,
+            unleet: unleeted,
+            scratch: Zeroizing::new(String::with_capacity(len * 4)),
+        }
+    }
+}
+
+impl Default for PasswordAuditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PasswordAuditor {
+    pub fn new() -> Self {
+        Self {
+            words: WORDLIST.lines().collect(),
+            common: COMMON_PASSWORDS
+                .lines()
+                .enumerate()
+                .map(|(rank, password)| (password, rank + 1))
+                .collect(),
+        }
+    }
+
+    fn common_rank(
+        &self,
+        scratch: &mut String,
+        chars: &[char],
+    ) -> Option<usize> {
+        self.common.get(fill(scratch, chars)).copied()
+    }
+
+    fn is_word(&self, scratch: &mut String, chars: &[char]) -> bool {
+        self.words.contains(fill(scratch, chars))
+    }
+
+    pub fn is_common(&self, password: &str) -> bool {
+        let Analyzed {
+            lower,
+            unleet,
+            mut scratch,
+            ..
+        } = Analyzed::new(password);
+
+        self.common_rank(&mut scratch, &lower).is_some()
+            || self.common_rank(&mut scratch, &unleet).is_some()
+    }
+
+    // Bits needed to guess `password[start..end]` as a single known pattern,
+    // if it is one.
+    fn pattern_bits(
+        &self,
+        password: &mut Analyzed,
+        start: usize,
+        end: usize,
+    ) -> Option<f64> {
+        let chars = &password.chars[start..end];
+        let lower = &password.lower[start..end];
+        let unleet = &password.unleet[start..end];
+        let scratch = &mut *password.scratch;
+        let uppercase = if chars != lower { 1.0 } else { 0.0 };
+        let leet = if unleet != lower { 1.0 } else { 0.0 };
+        let len = chars.len() as f64;
+
+        if let Some(rank) = self.common_rank(scratch, lower) {
+            return Some((rank as f64).log2() + uppercase);
+        }
+
+        if let Some(rank) = self.common_rank(scratch, unleet) {
+            return Some((rank as f64).log2() + uppercase + leet);
+        }
+
+        if chars.len() >= MIN_WORD_LENGTH
+            && (self.is_word(scratch, lower) || self.is_word(scratch, unleet))
+        {
+            return Some((self.words.len() as f64).log2() + uppercase + leet);
+        }
+
+        if chars.len() >= MIN_RUN_LENGTH && is_repeat(chars) {
+            return Some(class_size(chars[0]).log2() + len.log2());
+        }
+
+        if chars.len() >= MIN_RUN_LENGTH && is_sequence(chars) {
+            return Some(class_size(chars[0]).log2() + len.log2() + 1.0);
+        }
+
+        if chars.len() > MIN_RUN_LENGTH && is_keyboard_run(lower) {
+            return Some(KEYBOARD_KEYS.log2() + len.log2() + 1.0);
+        }
+
+        if is_year(chars) {
+            return Some(YEARS.log2());
+        }
+
+        None
+    }
+
+    // Estimates guessing entropy the way zxcvbn does: the password is split
+    // into the cheapest sequence of known patterns and random characters.
+    pub fn strength(&self, password: &str) -> Strength {
+        let mut password = Analyzed::new(password);
+        let len = password.chars.len();
+        let analyzed = len.min(MAX_ANALYZED);
+        let random_bits = charset_size(&password.chars).log2();
+        let mut best = vec![0.0; analyzed + 1];
+
+        for end in 1..=analyzed {
+            best[end] = best[end - 1] + random_bits;
+
+            for start in 0..end - 1 {
+                if let Some(bits) = self.pattern_bits(&mut password, start, end)
+                {
+                    best[end] = f64::min(best[end], best[start] + bits);
+                }
+            }
+        }
+
+        let bits = best[analyzed] + (len - analyzed) as f64 * random_bits;
+        let score = SCORE_BITS.iter().filter(|limit| bits >= **limit).count();
+
+        Strength {
+            score: score as u8,
+            bits,
+        }
+    }
+
+    pub fn findings(
+        &self,
+        relative_path: &Path,
+        password: &str,
+    ) -> Vec<DiagnosticResult> {
+        let mut findings = Vec::new();
+
+        if password.chars().count() < MIN_LENGTH {
+            findings.push(DiagnosticResult {
+                status: DiagnosticStatus::Warning,
+                issue: IssueType::ShortPassword,
+                message: format!(
+                    "Secret '{}' has a password shorter than {} characters",
+                    relative_path.display(),
+                    MIN_LENGTH
+                ),
+            });
+        }
+
+        if self.is_common(password) {
+            findings.push(DiagnosticResult {
+                status: DiagnosticStatus::Error,
+                issue: IssueType::CommonPassword,
+                message: format!(
+                    "Secret '{}' uses a commonly used password",
+                    relative_path.display()
+                ),
+            });
+
+            return findings;
+        }
+
+        let strength = self.strength(password);
+        let status = match strength.score {
+            0 | 1 => DiagnosticStatus::Error,
+            2 => DiagnosticStatus::Warning,
+            _ => return findings,
+        };
+
+        findings.push(DiagnosticResult {
+            status,
+            issue: IssueType::WeakPassword,
+            message: format!(
+                "Secret '{}' has a weak password (score {}/4, about {:.0} bits)",
+                relative_path.display(),
+                strength.score,
+                strength.bits
+            ),
+        });
+
+        findings
+    }
+}
+
+pub fn reuse_findings(
+    passwords: &[(PathBuf, SecretString)],
+    cancel: &CancellationToken,
+) -> Result<Vec<DiagnosticResult>> {
+    let mut findings = Vec::new();
+    let checksums: Vec<String> = passwords
+        .iter()
+        .map(|(_, password)| compute_checksum(password))
+        .collect();
+    let mut groups: HashMap<&str, Vec<&Path>> = HashMap::new();
+
+    for ((path, _), checksum) in passwords.iter().zip(&checksums) {
+        groups.entry(checksum).or_default().push(path);
+    }
+
+    for (path, checksum) in passwords.iter().map(|(p, _)| p).zip(&checksums) {
+        let others: Vec<String> = groups[checksum.as_str()]
+            .iter()
+            .filter(|other| **other != path.as_path())
+            .map(|other| format!("'{}'", other.display()))
+            .collect();
+
+        if !others.is_empty() {
+            findings.push(DiagnosticResult {
+                status: DiagnosticStatus::Error,
+                issue: IssueType::ReusedPassword,
+                message: format!(
+                    "Secret '{}' shares its password with {}",
+                    path.display(),
+                    others.join(", ")
+                ),
+            });
+        }
+    }
+
+    let lowered: Vec<Zeroizing<Vec<char>>> = passwords
+        .iter()
+        .map(|(_, password)| Analyzed::new(password).lower)
+        .collect();
+
+    for (i, (path, _)) in passwords.iter().enumerate() {
+        cancel.check()?;
+
+        for (j, (other, _)) in passwords.iter().enumerate() {
+            let (a, b) = (&lowered[i], &lowered[j]);
+
+            if i == j
+                || checksums[i] == checksums[j]
+                || a.len() < MIN_SIMILAR_LENGTH
+                || b.len() < MIN_SIMILAR_LENGTH
+                || char_edit_distance(a, b, MAX_SIMILAR_EDITS).is_none()
+            {
+                continue;
+            }
+
+            findings.push(DiagnosticResult {
+                status: DiagnosticStatus::Warning,
+                issue: IssueType::SimilarPassword,
+                message: format!(
+                    "Secret '{}' has a password similar to '{}'",
+                    path.display(),
+                    other.display()
+                ),
+            });
+        }
+    }
+
+    Ok(findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues(findings: &[DiagnosticResult]) -> Vec<String> {
+        findings
+            .iter()
+            .map(|finding| format!("{:?}", finding.issue))
+            .collect()
+    }
+
+    #[test]
+    fn common_passwords_are_found_with_and_without_leetspeak() {
+        let auditor = PasswordAuditor::new();
+
+        assert!(auditor.is_common("password"));
+        assert!(auditor.is_common("PassWord"));
+        assert!(auditor.is_common("P4$$w0rd"));
+        assert!(!auditor.is_common("P4$$w0rd-stapler"));
+        assert_eq!(
+            issues(&auditor.findings(Path::new("a"), "P4$$w0rd")),
+            ["ShortPassword", "CommonPassword"]
+        );
+    }
+
+    #[test]
+    fn keyboard_runs_and_years_are_cheap_to_guess() {
+        let auditor = PasswordAuditor::new();
+        let run = auditor.strength("lkjhgfdsa");
+        let year = auditor.strength("2043");
+
+        assert_eq!(run.score, 0);
+        assert!(run.bits < KEYBOARD_KEYS.log2() + 9f64.log2() + 1.0 + 1e-9);
+        assert_eq!(year.score, 0);
+        assert!((year.bits - YEARS.log2()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn random_passwords_score_highest() {
+        let auditor = PasswordAuditor::new();
+        let strength = auditor.strength("x7#Qm9$Lp2!vRt8&Kw4Z");
+
+        assert_eq!(strength.score, 4);
+        assert!(strength.bits > 100.0);
+        assert!(
+            auditor
+                .findings(Path::new("a"), "x7#Qm9$Lp2!vRt8&Kw4Z")
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn reused_and_similar_passwords_are_reported_for_each_secret() {
+        let passwords: Vec<(PathBuf, SecretString)> = [
+            ("a", "correct-horse-battery"),
+            ("b", "correct-horse-battery"),
+            ("c", "Correct-horse-battery1"),
+            ("d", "unrelated-stapler-xyz"),
+        ]
+        .into_iter()
+        .map(|(path, password)| (PathBuf::from(path), password.into()))
+        .collect();
+        let findings =
+            reuse_findings(&passwords, &CancellationToken::new()).unwrap();
+        let messages: Vec<&str> =
+            findings.iter().map(|f| f.message.as_str()).collect();
+
+        assert_eq!(
+            messages,
+            [
+                "Secret 'a' shares its password with 'b'",
+                "Secret 'b' shares its password with 'a'",
+                "Secret 'a' has a password similar to 'c'",
+                "Secret 'b' has a password similar to 'c'",
+                "Secret 'c' has a password similar to 'a'",
+                "Secret 'c' has a password similar to 'b'",
+            ]
+        );
+        assert_eq!(
+            issues(&findings),
+            [
+                "ReusedPassword",
+                "ReusedPassword",
+                "SimilarPassword",
+                "SimilarPassword",
+                "SimilarPassword",
+                "SimilarPassword",
+            ]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+use std::{
+    cmp::Ordering,
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    path::PathBuf,
+};
+
+use md4::{Digest, Md4};
+use serde::Deserialize;
+use sha1::Sha1;
+use zeroize::Zeroizing;
+
+use crate::error::{IoContext, Result};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BreachHash {
+    #[default]
+    Sha1,
+    Ntlm,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BreachCorpusConfig {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub hash: BreachHash,
+}
+
+// A local copy of a breached-password hash list, one `HASH:COUNT` line per
+// password and sorted by hash, as published by Have I Been Pwned.
+pub struct BreachCorpus {
+    reader: BufReader<File>,
+    len: u64,
+    hash: BreachHash,
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789ABCDEF";
+
+// The hash stands in for the password, so it is kept in a wiped buffer too.
+fn to_hex(bytes: &[u8]) -> Zeroizing<String> {
+    let mut hex = Zeroizing::new(String::with_capacity(bytes.len() * 2));
+
+    for byte in bytes {
+        hex.push(HEX_DIGITS[usize::from(byte >> 4)] as char);
+        hex.push(HEX_DIGITS[usize::from(byte & 0x0f)] as char);
+    }
+
+    hex
+}
+
+impl BreachHash {
+    fn hash(self, password: &str) -> Zeroizing<String> {
+        match self {
+            BreachHash::Sha1 => to_hex(&Sha1::digest(password.as_bytes())),
+            // NTLM is MD4 over the UTF-16LE password, fed one code unit at a
+            // time so no encoded copy of the password is ever built.
+            BreachHash::Ntlm => {
+                let mut hasher = Md4::new();
+
+                for unit in password.encode_utf16() {
+                    hasher.update(unit.to_le_bytes());
+                }
+
+                to_hex(&hasher.finalize())
+            }
+        }
+    }
+}
+
+impl BreachCorpus {
+    pub fn open(config: &BreachCorpusConfig) -> Result<Self> {
+        let file = File::open(&config.path).io_context(format!(
+            "Failed to open breach corpus {}",
+            config.path.display()
+        ))?;
+        let len = file
+            .metadata()
+            .io_context("Failed to read breach corpus metadata")?
+            .len();
+
+        Ok(Self {
+            reader: BufReader::new(file),
+            len,
+            hash: config.hash,
+        })
+    }
+
+    // Returns the first line starting at or after `offset` together with
+    // the offset just past it.
+    fn line_after(&mut self, offset: u64) -> Result<Option<(String, u64)>> {
+        let mut line = String::new();
+        let mut position = offset;
+
+        if offset > 0 {
+            self.reader
+                .seek(SeekFrom::Start(offset - 1))
+                .io_context("Failed to seek in breach corpus")?;
+            position += self
+                .reader
+                .read_line(&mut line)
+                .io_context("Failed to read breach corpus")?
+                as u64
+                - 1;
+            line.clear();
+        } else {
+            self.reader
+                .seek(SeekFrom::Start(0))
+                .io_context("Failed to seek in breach corpus")?;
+        }
+
+        let read = self
+            .reader
+            .read_line(&mut line)
+            .io_context("Failed to read breach corpus")?;
+
+        if read == 0 {
+            return Ok(None);
+        }
+
+        Ok(Some((line, position + read as u64)))
+    }
+
+    // Binary search over byte offsets, so only a few dozen lines are read
+    // no matter how large the corpus is.
+    pub fn lookup(&mut self, password: &str) -> Result<Option<u64>> {
+        let target = self.hash.hash(password);
+        let (mut low, mut high) = (0, self.len);
+
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let Some((line, end)) = self.line_after(middle)? else {
+                high = middle;
+                continue;
+            };
+            let (hash, count) = line
+                .trim_end()
+                .split_once(':')
+                .unwrap_or((line.trim_end(), ""));
+
+            match hash.to_ascii_uppercase().as_str().cmp(target.as_str()) {
+                Ordering::Less => low = end,
+                Ordering::Greater => high = middle,
+                Ordering::Equal => {
+                    return Ok(Some(count.trim().parse().unwrap_or(1)));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    // RFC 1320, Appendix A.5.
+    #[test]
+    fn md4_matches_rfc1320_vectors() {
+        let vectors = [
+            ("", "31D6CFE0D16AE931B73C59D7E0C089C0"),
+            ("a", "BDE52CB31DE33E46245E05FBDBD6FB24"),
+            ("abc", "A448017AAF21D8525FC10AE87AA6729D"),
+            ("message digest", "D9130A8164549FE818874806E1C7014B"),
+            (
+                "abcdefghijklmnopqrstuvwxyz",
+                "D79E1C308AA5BBCDEEA8ED63DF412DA9",
+            ),
+            (
+                "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+                "043F8582F241DB351CE627E153E7F0E4",
+            ),
+            (
+                "12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+                "E33B4DDC9C38F2199C3E7B164FCC0536",
+            ),
+        ];
+
+        for (message, digest) in vectors {
+            assert_eq!(
+                *to_hex(&Md4::digest(message.as_bytes())),
+                digest,
+                "{message:?}"
+            );
+        }
+
+        assert_eq!(
+            *BreachHash::Ntlm.hash("password"),
+            "8846F7EAEE8FB117AD06BDD830B7586C"
+        );
+        assert_eq!(
+            *BreachHash::Ntlm.hash("pässwörd€"),
+            *to_hex(&Md4::digest(
+                "pässwörd€"
+                    .encode_utf16()
+                    .flat_map(u16::to_le_bytes)
+                    .collect::<Vec<u8>>()
+            ))
+        );
+    }
+
+    fn corpus(
+        dir: &tempfile::TempDir,
+        passwords: &[(&str, u64)],
+        trailing_newline: bool,
+    ) -> BreachCorpus {
+        let mut lines: Vec<String> = passwords
+            .iter()
+            .map(|(password, count)| {
+                format!("{}:{count}", *BreachHash::Sha1.hash(password))
+            })
+            .collect();
+
+        lines.sort();
+
+        let mut text = lines.join("\n");
+
+        if trailing_newline {
+            text.push('\n');
+        }
+
+        let path = dir.path().join("corpus.txt");
+
+        fs::write(&path, text).unwrap();
+
+        BreachCorpus::open(&BreachCorpusConfig {
+            path,
+            hash: BreachHash::Sha1,
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn lookup_finds_every_line_of_a_sorted_corpus() {
+        // Sorted by SHA-1, "password" comes first and "letmein" last.
+        let passwords = [
+            ("password", 9545824),
+            ("abc123", 7),
+            ("123456", 37359195),
+            ("monkey", 1),
+            ("dragon", 25),
+            ("qwerty", 10000),
+            ("letmein", 3),
+        ];
+
+        for trailing_newline in [true, false] {
+            let dir = tempfile::tempdir().unwrap();
+            let mut corpus = corpus(&dir, &passwords, trailing_newline);
+
+            for (password, count) in passwords {
+                assert_eq!(
+                    corpus.lookup(password).unwrap(),
+                    Some(count),
+                    "{password} with trailing newline {trailing_newline}"
+                );
+            }
+
+            for missing in ["hunter2", "", "zzzzzzzzzz"] {
+                assert_eq!(corpus.lookup(missing).unwrap(), None, "{missing}");
+            }
+        }
+    }
+}
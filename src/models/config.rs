@@ -1,4 +1,4 @@
-use std::{env, net::IpAddr, path::PathBuf};
+use std::{collections::HashMap, env, net::IpAddr, path::PathBuf};
 
 use anyhow::{Context, Result};
 use config::{Config as RawConfig, File};
@@ -6,7 +6,9 @@ use directories::BaseDirs;
 use log::LevelFilter;
 use serde::Deserialize;
 
-use crate::models::metadata::BaseMetadata;
+use crate::models::{
+    breach::BreachCorpusConfig, metadata::BaseMetadata, schema::SchemaConfig,
+};
 
 #[derive(Debug, Clone, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -41,6 +43,13 @@ pub struct Config {
     pub address: IpAddr,
     pub port: u16,
     pub metadata_template: Option<BaseMetadata>,
+    #[serde(default)]
+    pub templates: HashMap<String, BaseMetadata>,
+    #[serde(default)]
+    pub schemas: SchemaConfig,
+    #[serde(default)]
+    pub encrypted_fields: Vec<String>,
+    pub breach_corpus: Option<BreachCorpusConfig>,
 }
 
 impl Default for Config {
@@ -60,11 +69,26 @@ impl Default for Config {
             address: "127.0.0.1".parse().unwrap(),
             port: 7117,
             metadata_template: Some(BaseMetadata::default()),
+            templates: HashMap::new(),
+            schemas: SchemaConfig::default(),
+            encrypted_fields: Vec::new(),
+            breach_corpus: None,
         }
     }
 }
 
 impl Config {
+    pub fn template_for(
+        &self,
+        metadata: &BaseMetadata,
+    ) -> Option<&BaseMetadata> {
+        [&metadata.r#type, &metadata.category]
+            .into_iter()
+            .flatten()
+            .find_map(|key| self.templates.get(key))
+            .or(self.metadata_template.as_ref())
+    }
+
     pub fn load_config() -> Result<Self> {
         let default_config = Self::default();
         let config_path = [
@@ -119,3 +143,58 @@ impl Config {
         Ok(config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(text: &str) -> BaseMetadata {
+        toml::from_str(text).unwrap()
+    }
+
+    fn description(template: Option<&BaseMetadata>) -> Option<&str> {
+        template?.description.as_deref()
+    }
+
+    #[test]
+    fn templates_are_chosen_by_type_then_category() {
+        let config = Config {
+            metadata_template: Some(metadata(r#"description = "default""#)),
+            templates: toml::from_str(
+                r#"
+                [login]
+                description = "login"
+
+                [work]
+                description = "work"
+                "#,
+            )
+            .unwrap(),
+            ..Config::default()
+        };
+        let template =
+            |text: &str| description(config.template_for(&metadata(text)));
+
+        assert_eq!(template(r#"type = "login""#), Some("login"));
+        assert_eq!(
+            template("type = \"login\"\ncategory = \"work\""),
+            Some("login")
+        );
+        assert_eq!(
+            template("type = \"note\"\ncategory = \"work\""),
+            Some("work")
+        );
+        assert_eq!(template(r#"type = "note""#), Some("default"));
+        assert_eq!(template(""), Some("default"));
+
+        let config = Config {
+            metadata_template: None,
+            ..config
+        };
+
+        assert_eq!(
+            description(config.template_for(&metadata(r#"type = "note""#))),
+            None
+        );
+    }
+}
@@ -0,0 +1,608 @@
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use toml::{Table, Value as TomlValue};
+use zeroize::Zeroize;
+
+use crate::{
+    error::{Error, Result},
+    utils::memory::{SecretBuffer, SecretString},
+};
+
+pub const PASSWORD_FIELD: &str = "password";
+
+// Recorded in the metadata as `content_format`; secrets without one are
+// pass-style, which any text is valid as.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentFormat {
+    #[default]
+    Pass,
+    Json,
+    Toml,
+}
+
+impl ContentFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            ContentFormat::Pass => "pass",
+            ContentFormat::Json => "json",
+            ContentFormat::Toml => "toml",
+        }
+    }
+}
+
+enum Document {
+    Pass(Vec<String>),
+    Json(Map<String, Value>),
+    Toml(Table),
+}
+
+// Parsed plaintext content, wiped on drop like the `SecretString` it came
+// from.
+pub struct SecretContent {
+    document: Document,
+}
+
+const RENDER_CAPACITY: usize = 1024;
+
+fn render_error(e: impl std::fmt::Display) -> Error {
+    Error::Internal(format!("Failed to serialize content: {e}"))
+}
+
+// Everything written comes from `&str`s, so the output is always UTF-8.
+fn into_secret_string(out: SecretBuffer) -> SecretString {
+    out.into_secret_string()
+        .expect("content is rendered from valid UTF-8")
+}
+
+fn field_not_found(field: &str) -> Error {
+    Error::InvalidParams(format!("Content field '{field}' does not exist"))
+}
+
+fn not_a_table(field: &str) -> Error {
+    Error::InvalidParams(format!(
+        "Content field '{field}' is not inside a table"
+    ))
+}
+
+// A pass-style field line is `key: value` with a key free of whitespace and
+// slashes and a value that does not start with `//`, so URLs and free-form
+// notes are not mistaken for fields.
+fn pass_field(line: &str) -> Option<(&str, &str)> {
+    let (key, value) = line.split_once(':')?;
+
+    if key.is_empty()
+        || key.contains(|c: char| c.is_whitespace() || c == '/')
+        || value.starts_with("//")
+    {
+        return None;
+    }
+
+    Some((key, value.trim()))
+}
+
+fn json_get<'a>(map: &'a Map<String, Value>, field: &str) -> Option<&'a Value> {
+    let mut keys = field.split('.');
+    let first = map.get(keys.next()?)?;
+
+    keys.try_fold(first, |value, key| value.as_object()?.get(key))
+}
+
+fn toml_get<'a>(table: &'a Table, field: &str) -> Option<&'a TomlValue> {
+    let mut keys = field.split('.');
+    let first = table.get(keys.next()?)?;
+
+    keys.try_fold(first, |value, key| value.as_table()?.get(key))
+}
+
+fn json_set(
+    map: &mut Map<String, Value>,
+    field: &str,
+    value: &str,
+) -> Result<()> {
+    let (parents, key) = match field.rsplit_once('.') {
+        Some((parents, key)) => (parents.split('.').collect(), key),
+        None => (Vec::new(), field),
+    };
+    let mut current = map;
+
+    for parent in parents {
+        current = current
+            .entry(parent)
+            .or_insert_with(|| Value::Object(Map::new()))
+            .as_object_mut()
+            .ok_or_else(|| not_a_table(field))?;
+    }
+
+    if let Some(mut old) = current.insert(key.to_string(), Value::from(value)) {
+        zeroize_json(&mut old);
+    }
+
+    Ok(())
+}
+
+fn toml_set(table: &mut Table, field: &str, value: &str) -> Result<()> {
+    let (parents, key) = match field.rsplit_once('.') {
+        Some((parents, key)) => (parents.split('.').collect(), key),
+        None => (Vec::new(), field),
+    };
+    let mut current = table;
+
+    for parent in parents {
+        current = current
+            .entry(parent)
+            .or_insert_with(|| TomlValue::Table(Table::new()))
+            .as_table_mut()
+            .ok_or_else(|| not_a_table(field))?;
+    }
+
+    if let Some(mut old) =
+        current.insert(key.to_string(), TomlValue::from(value))
+    {
+        zeroize_toml(&mut old);
+    }
+
+    Ok(())
+}
+
+fn zeroize_json(value: &mut Value) {
+    match value {
+        Value::String(s) => s.zeroize(),
+        Value::Array(items) => items.iter_mut().for_each(zeroize_json),
+        Value::Object(map) => map.values_mut().for_each(zeroize_json),
+        _ => {}
+    }
+}
+
+fn zeroize_toml(value: &mut TomlValue) {
+    match value {
+        TomlValue::String(s) => s.zeroize(),
+        TomlValue::Array(items) => items.iter_mut().for_each(zeroize_toml),
+        TomlValue::Table(table) => {
+            table.iter_mut().for_each(|(_, value)| zeroize_toml(value))
+        }
+        _ => {}
+    }
+}
+
+// The `toml` serializer assembles documents in buffers of its own that are
+// freed without being wiped, so TOML content is written out by hand.
+fn write_toml_key(out: &mut SecretBuffer, key: &str) -> std::fmt::Result {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+
+    if bare {
+        out.write_str(key)
+    } else {
+        write_toml_string(out, key)
+    }
+}
+
+fn write_toml_string(out: &mut SecretBuffer, value: &str) -> std::fmt::Result {
+    out.write_char('"')?;
+
+    for c in value.chars() {
+        match c {
+            '"' => out.write_str("\\\"")?,
+            '\\' => out.write_str("\\\\")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            '\t' => out.write_str("\\t")?,
+            '\u{8}' => out.write_str("\\b")?,
+            '\u{c}' => out.write_str("\\f")?,
+            c if c.is_control() => write!(out, "\\u{:04X}", c as u32)?,
+            c => out.write_char(c)?,
+        }
+    }
+
+    out.write_char('"')
+}
+
+fn write_toml_value(
+    out: &mut SecretBuffer,
+    value: &TomlValue,
+) -> std::fmt::Result {
+    match value {
+        TomlValue::String(s) => write_toml_string(out, s),
+        TomlValue::Integer(i) => write!(out, "{i}"),
+        TomlValue::Float(f) if f.is_nan() => out.write_str("nan"),
+        TomlValue::Float(f) if f.is_infinite() => {
+            out.write_str(if *f > 0.0 { "inf" } else { "-inf" })
+        }
+        TomlValue::Float(f) => write!(out, "{f:?}"),
+        TomlValue::Boolean(b) => write!(out, "{b}"),
+        TomlValue::Datetime(dt) => write!(out, "{dt}"),
+        TomlValue::Array(items) => {
+            out.write_char('[')?;
+
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.write_str(", ")?;
+                }
+
+                write_toml_value(out, item)?;
+            }
+
+            out.write_char(']')
+        }
+        TomlValue::Table(table) => {
+            out.write_char('{')?;
+
+            for (i, (key, value)) in table.iter().enumerate() {
+                out.write_str(if i > 0 { ", " } else { " " })?;
+                write_toml_key(out, key)?;
+                out.write_str(" = ")?;
+                write_toml_value(out, value)?;
+            }
+
+            out.write_str(if table.is_empty() { "}" } else { " }" })
+        }
+    }
+}
+
+// Plain values come first, then each nested table under its own header.
+fn write_toml_table(
+    out: &mut SecretBuffer,
+    path: &mut Vec<String>,
+    table: &Table,
+) -> std::fmt::Result {
+    for (key, value) in table.iter().filter(|(_, value)| !value.is_table()) {
+        write_toml_key(out, key)?;
+        out.write_str(" = ")?;
+        write_toml_value(out, value)?;
+        out.write_char('\n')?;
+    }
+
+    for (key, value) in table.iter() {
+        let TomlValue::Table(nested) = value else {
+            continue;
+        };
+
+        path.push(key.clone());
+
+        if out.is_empty() {
+            out.write_char('[')?;
+        } else {
+            out.write_str("\n[")?;
+        }
+
+        for (i, key) in path.iter().enumerate() {
+            if i > 0 {
+                out.write_char('.')?;
+            }
+
+            write_toml_key(out, key)?;
+        }
+
+        out.write_str("]\n")?;
+        write_toml_table(out, path, nested)?;
+        path.pop();
+    }
+
+    Ok(())
+}
+
+impl SecretContent {
+    // The format is never guessed from the text, since a password such as
+    // `pw=123456` is a valid TOML document. Parse errors leave out the
+    // parser's message, which may quote the content.
+    pub fn parse(content: &str, format: ContentFormat) -> Result<Self> {
+        let invalid = || {
+            Error::InvalidParams(format!(
+                "Content is not a valid {} document",
+                format.name()
+            ))
+        };
+        let document = match format {
+            ContentFormat::Pass => Document::Pass(
+                content.split('\n').map(str::to_string).collect(),
+            ),
+            ContentFormat::Json => Document::Json(
+                serde_json::from_str(content).map_err(|_| invalid())?,
+            ),
+            ContentFormat::Toml => {
+                Document::Toml(content.parse().map_err(|_| invalid())?)
+            }
+        };
+
+        Ok(Self { document })
+    }
+
+    pub fn get(&self, field: &str) -> Result<SecretString> {
+        match &self.document {
+            Document::Pass(lines) if field == PASSWORD_FIELD => {
+                let first = lines.first().map_or("", |line| line.trim_end());
+
+                Ok(SecretString::from(first))
+            }
+            Document::Pass(lines) => lines
+                .iter()
+                .skip(1)
+                .filter_map(|line| pass_field(line))
+                .find(|(key, _)| key.eq_ignore_ascii_case(field))
+                .map(|(_, value)| SecretString::from(value))
+                .ok_or_else(|| field_not_found(field)),
+            Document::Json(map) => match json_get(map, field) {
+                Some(Value::String(value)) => {
+                    Ok(SecretString::from(value.as_str()))
+                }
+                Some(value) => {
+                    let mut out = SecretBuffer::default();
+
+                    serde_json::to_writer(&mut out, value)
+                        .map_err(render_error)?;
+
+                    Ok(into_secret_string(out))
+                }
+                None => Err(field_not_found(field)),
+            },
+            Document::Toml(table) => match toml_get(table, field) {
+                Some(TomlValue::String(value)) => {
+                    Ok(SecretString::from(value.as_str()))
+                }
+                Some(value) => {
+                    let mut out = SecretBuffer::default();
+
+                    write_toml_value(&mut out, value).map_err(render_error)?;
+
+                    Ok(into_secret_string(out))
+                }
+                None => Err(field_not_found(field)),
+            },
+        }
+    }
+
+    pub fn set(&mut self, field: &str, value: &str) -> Result<()> {
+        match &mut self.document {
+            Document::Pass(_) if value.contains('\n') => {
+                Err(Error::InvalidParams(format!(
+                    "Content field '{field}' must be a single line"
+                )))
+            }
+            Document::Pass(lines) if field == PASSWORD_FIELD => {
+                match lines.first_mut() {
+                    Some(first) => {
+                        first.zeroize();
+                        first.push_str(value);
+                    }
+                    None => lines.push(value.to_string()),
+                }
+
+                Ok(())
+            }
+            Document::Pass(lines) => {
+                if field.is_empty()
+                    || field.contains(|c: char| c.is_whitespace() || c == ':')
+                {
+                    return Err(Error::InvalidParams(format!(
+                        "Invalid content field name '{field}'"
+                    )));
+                }
+
+                let mut line =
+                    String::with_capacity(field.len() + 2 + value.len());
+
+                line.push_str(field);
+                line.push_str(": ");
+                line.push_str(value);
+
+                let existing = lines.iter().skip(1).position(|line| {
+                    pass_field(line)
+                        .is_some_and(|(key, _)| key.eq_ignore_ascii_case(field))
+                });
+
+                match existing {
+                    Some(index) => {
+                        let mut old =
+                            std::mem::replace(&mut lines[index + 1], line);
+
+                        old.zeroize();
+                    }
+                    // Keep a trailing newline at the end of the content.
+                    None if lines.len() > 1
+                        && lines.last().is_some_and(String::is_empty) =>
+                    {
+                        lines.insert(lines.len() - 1, line);
+                    }
+                    None => lines.push(line),
+                }
+
+                Ok(())
+            }
+            Document::Json(map) => json_set(map, field, value),
+            Document::Toml(table) => toml_set(table, field, value),
+        }
+    }
+
+    // Rendered into a `SecretBuffer` so no unwiped copy of the plaintext is
+    // left behind when the output grows.
+    pub fn render(&self) -> Result<SecretString> {
+        let out = match &self.document {
+            Document::Pass(lines) => {
+                let len = lines.iter().map(|line| line.len() + 1).sum();
+                let mut out = SecretBuffer::with_capacity(len);
+
+                for (i, line) in lines.iter().enumerate() {
+                    if i > 0 {
+                        out.extend_from_slice(b"\n");
+                    }
+
+                    out.extend_from_slice(line.as_bytes());
+                }
+
+                out
+            }
+            Document::Json(map) => {
+                let mut out = SecretBuffer::with_capacity(RENDER_CAPACITY);
+
+                serde_json::to_writer_pretty(&mut out, map)
+                    .map_err(render_error)?;
+
+                out
+            }
+            Document::Toml(table) => {
+                let mut out = SecretBuffer::with_capacity(RENDER_CAPACITY);
+
+                write_toml_table(&mut out, &mut Vec::new(), table)
+                    .map_err(render_error)?;
+
+                out
+            }
+        };
+
+        Ok(into_secret_string(out))
+    }
+}
+
+impl Drop for SecretContent {
+    fn drop(&mut self) {
+        match &mut self.document {
+            Document::Pass(lines) => {
+                lines.iter_mut().for_each(Zeroize::zeroize)
+            }
+            Document::Json(map) => map.values_mut().for_each(zeroize_json),
+            Document::Toml(table) => {
+                table.iter_mut().for_each(|(_, value)| zeroize_toml(value))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passwords_that_look_like_documents_stay_pass_style() {
+        for password in ["pw=123456", "a = \"b\"", "{\"a\": 1}"] {
+            let content =
+                SecretContent::parse(password, ContentFormat::Pass).unwrap();
+
+            assert_eq!(&*content.get(PASSWORD_FIELD).unwrap(), password);
+        }
+    }
+
+    #[test]
+    fn content_must_match_its_declared_format() {
+        let error = SecretContent::parse("hunter2", ContentFormat::Json)
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.to_string(),
+            "Invalid parameters: Content is not a valid json document"
+        );
+        assert!(SecretContent::parse("hunter2", ContentFormat::Toml).is_err());
+    }
+
+    fn render(content: &SecretContent) -> String {
+        content.render().unwrap().to_string()
+    }
+
+    #[test]
+    fn pass_content_reads_and_writes_fields() {
+        let text = "hunter2\nUser: alice\nhttps://example.com/login\n";
+        let mut content =
+            SecretContent::parse(text, ContentFormat::Pass).unwrap();
+
+        assert_eq!(render(&content), text);
+        assert_eq!(&*content.get(PASSWORD_FIELD).unwrap(), "hunter2");
+        assert_eq!(&*content.get("user").unwrap(), "alice");
+        assert!(content.get("https").is_err());
+
+        content.set(PASSWORD_FIELD, "correct horse").unwrap();
+        content.set("user", "bob").unwrap();
+        content.set("otp", "123456").unwrap();
+
+        assert_eq!(
+            render(&content),
+            "correct horse\nuser: bob\nhttps://example.com/login\n\
+             otp: 123456\n"
+        );
+        assert!(content.set("otp", "two\nlines").is_err());
+        assert!(content.set("bad key", "value").is_err());
+    }
+
+    #[test]
+    fn json_content_reads_and_writes_dotted_fields() {
+        let text = r#"{"password": "hunter2", "login": {"user": "alice"},
+            "pin": 1234, "tags": ["a", "b"]}"#;
+        let mut content =
+            SecretContent::parse(text, ContentFormat::Json).unwrap();
+
+        assert_eq!(&*content.get(PASSWORD_FIELD).unwrap(), "hunter2");
+        assert_eq!(&*content.get("login.user").unwrap(), "alice");
+        assert_eq!(&*content.get("pin").unwrap(), "1234");
+        assert_eq!(&*content.get("tags").unwrap(), r#"["a","b"]"#);
+        assert!(content.get("login.missing").is_err());
+
+        content.set("login.user", "bob").unwrap();
+        content.set("server.host", "example.com").unwrap();
+        assert!(content.set("password.inner", "x").is_err());
+
+        let rendered: Value = serde_json::from_str(&render(&content)).unwrap();
+
+        assert_eq!(
+            rendered,
+            serde_json::json!({
+                "password": "hunter2",
+                "login": {"user": "bob"},
+                "pin": 1234,
+                "tags": ["a", "b"],
+                "server": {"host": "example.com"},
+            })
+        );
+    }
+
+    #[test]
+    fn toml_content_reads_and_writes_dotted_fields() {
+        let text = "password = \"hunter2\"\npin = 1234\n\n\
+                    [login]\nuser = \"alice\"\n";
+        let mut content =
+            SecretContent::parse(text, ContentFormat::Toml).unwrap();
+
+        assert_eq!(&*content.get(PASSWORD_FIELD).unwrap(), "hunter2");
+        assert_eq!(&*content.get("login.user").unwrap(), "alice");
+        assert_eq!(&*content.get("pin").unwrap(), "1234");
+        assert!(content.get("login.missing").is_err());
+
+        content.set("login.user", "bob").unwrap();
+        content.set("server.host", "example.com").unwrap();
+        assert!(content.set("password.inner", "x").is_err());
+
+        assert_eq!(
+            render(&content),
+            "password = \"hunter2\"\npin = 1234\n\n[login]\n\
+             user = \"bob\"\n\n[server]\nhost = \"example.com\"\n"
+        );
+    }
+
+    #[test]
+    fn toml_content_round_trips_every_kind_of_value() {
+        let text = r#"
+            "key with spaces" = "quote \" backslash \\ tab \t bell \u0007"
+            float = 1.5
+            big = 1e300
+            infinite = -inf
+            yes = true
+            when = 2024-05-01T12:30:00Z
+            day = 2024-05-01
+            list = [1, "two", [3], { four = 4 }]
+            empty = {}
+
+            [outer."in.ner"]
+            deep = "value"
+        "#;
+        let content = SecretContent::parse(text, ContentFormat::Toml).unwrap();
+        let expected: Table = text.parse().unwrap();
+        let rendered: Table = render(&content).parse().unwrap();
+
+        assert_eq!(rendered, expected);
+    }
+}
@@ -0,0 +1,420 @@
+use std::cmp::Ordering;
+
+use globset::{Glob, GlobMatcher};
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
+use serde_json::Value;
+
+use crate::{
+    models::{metadata::Metadata, query::parse_query},
+    utils::time::parse_datetime,
+};
+
+#[derive(Debug, Clone)]
+pub struct RegexPattern(Regex);
+
+#[derive(Debug, Clone)]
+pub struct GlobPattern(GlobMatcher);
+
+impl RegexPattern {
+    pub fn new(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(pattern).map(Self)
+    }
+}
+
+impl GlobPattern {
+    pub fn new(pattern: &str) -> Result<Self, globset::Error> {
+        Glob::new(pattern).map(|glob| Self(glob.compile_matcher()))
+    }
+}
+
+impl Serialize for RegexPattern {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for RegexPattern {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        Self::new(&String::deserialize(deserializer)?)
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Serialize for GlobPattern {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0.glob().glob())
+    }
+}
+
+impl<'de> Deserialize<'de> for GlobPattern {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        Self::new(&String::deserialize(deserializer)?)
+            .map_err(de::Error::custom)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", tag = "op", content = "value")]
+pub enum Operator {
+    Eq(Value),
+    Ne(Value),
+    Gt(Value),
+    Gte(Value),
+    Lt(Value),
+    Lte(Value),
+    In(Vec<Value>),
+    Exists(bool),
+    Contains(Value),
+    #[serde(rename = "starts_with")]
+    StartsWith(String),
+    Glob(GlobPattern),
+    Regex(RegexPattern),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FieldCondition {
+    pub field: String,
+    #[serde(flatten)]
+    pub operator: Operator,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    Not(Box<Filter>),
+    And(Vec<Filter>),
+    Or(Vec<Filter>),
+    Condition(FieldCondition),
+}
+
+pub fn compare_values(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Number(a), Value::Number(b)) => {
+            if let (Some(a), Some(b)) = (a.as_f64(), b.as_f64()) {
+                a.partial_cmp(&b)
+            } else {
+                None
+            }
+        }
+        (Value::String(a), Value::String(b)) => {
+            match (parse_datetime(a), parse_datetime(b)) {
+                (Some(a), Some(b)) => Some(a.cmp(&b)),
+                _ => Some(a.cmp(b)),
+            }
+        }
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+/// Orders any two values so that sorting on a sparsely populated or mixed-type
+/// field stays consistent: values are ranked by type first, with timestamps
+/// before other strings and `null` last, then compared within their type.
+pub fn total_order(a: &Value, b: &Value) -> Ordering {
+    fn rank(value: &Value) -> u8 {
+        match value {
+            Value::Bool(_) => 0,
+            Value::Number(_) => 1,
+            Value::String(s) if parse_datetime(s).is_some() => 2,
+            Value::String(_) => 3,
+            Value::Array(_) => 4,
+            Value::Object(_) => 5,
+            Value::Null => 6,
+        }
+    }
+
+    rank(a).cmp(&rank(b)).then_with(|| match (a, b) {
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| total_order(a, b))
+            .find(|ord| ord.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        _ => compare_values(a, b).unwrap_or(Ordering::Equal),
+    })
+}
+
+fn values_equal(a: &Value, b: &Value) -> bool {
+    a == b || compare_values(a, b) == Some(Ordering::Equal)
+}
+
+fn any_string(value: &Value, predicate: impl Fn(&str) -> bool) -> bool {
+    match value {
+        Value::String(s) => predicate(s),
+        Value::Array(items) => {
+            items.iter().filter_map(Value::as_str).any(predicate)
+        }
+        _ => false,
+    }
+}
+
+impl Operator {
+    pub fn matches(&self, value: Option<&Value>) -> bool {
+        let Some(value) = value else {
+            return matches!(self, Operator::Ne(_) | Operator::Exists(false));
+        };
+
+        match self {
+            Operator::Exists(expected) => *expected,
+            Operator::Eq(target) => values_equal(value, target),
+            Operator::Ne(target) => !values_equal(value, target),
+            Operator::Gt(target) => {
+                compare_values(value, target) == Some(Ordering::Greater)
+            }
+            Operator::Gte(target) => matches!(
+                compare_values(value, target),
+                Some(Ordering::Greater | Ordering::Equal)
+            ),
+            Operator::Lt(target) => {
+                compare_values(value, target) == Some(Ordering::Less)
+            }
+            Operator::Lte(target) => matches!(
+                compare_values(value, target),
+                Some(Ordering::Less | Ordering::Equal)
+            ),
+            Operator::In(targets) => match value {
+                Value::Array(items) => items
+                    .iter()
+                    .any(|item| targets.iter().any(|t| values_equal(item, t))),
+                _ => targets.iter().any(|t| values_equal(value, t)),
+            },
+            Operator::Contains(needle) => match (value, needle) {
+                (Value::Array(haystack), Value::Array(needles)) => {
+                    needles.iter().any(|n| haystack.contains(n))
+                }
+                (Value::Array(haystack), _) => haystack.contains(needle),
+                (Value::String(hay), Value::String(needle)) => {
+                    hay.contains(needle)
+                }
+                _ => value == needle,
+            },
+            Operator::StartsWith(prefix) => {
+                any_string(value, |s| s.starts_with(prefix.as_str()))
+            }
+            Operator::Glob(GlobPattern(matcher)) => {
+                any_string(value, |s| matcher.is_match(s))
+            }
+            Operator::Regex(RegexPattern(re)) => {
+                any_string(value, |s| re.is_match(s))
+            }
+        }
+    }
+}
+
+pub fn combine_filter(
+    filter: Option<Filter>,
+    query: Option<String>,
+) -> crate::Result<Option<Filter>> {
+    let query = query.map(|q| parse_query(&q)).transpose()?;
+
+    Ok(match (filter, query) {
+        (Some(filter), Some(query)) => Some(Filter::And(vec![filter, query])),
+        (filter, query) => filter.or(query),
+    })
+}
+
+impl Filter {
+    pub fn matches(&self, meta: &Metadata) -> bool {
+        match self {
+            Filter::Not(filter) => !filter.matches(meta),
+            Filter::And(filters) => filters.iter().all(|f| f.matches(meta)),
+            Filter::Or(filters) => filters.iter().any(|f| f.matches(meta)),
+            Filter::Condition(cond) => {
+                let value = meta
+                    .get_field(&cond.field)
+                    .ok()
+                    .flatten()
+                    .filter(|value| !value.is_null());
+
+                cond.operator.matches(value.as_ref())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use chrono::{Duration, Utc};
+    use serde_json::json;
+
+    use super::*;
+    use crate::models::metadata::BaseMetadata;
+
+    fn operator(value: Value) -> Operator {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn operators_match_scalars_and_arrays() {
+        let tags = json!(["work", "mail"]);
+        let cases = [
+            (json!({"op": "ne", "value": "home"}), json!("work"), true),
+            (json!({"op": "ne", "value": "work"}), json!("work"), false),
+            (json!({"op": "gte", "value": 3}), json!(3), true),
+            (json!({"op": "gte", "value": 3}), json!(2.5), false),
+            (json!({"op": "lte", "value": 3}), json!(3.0), true),
+            (
+                json!({"op": "in", "value": ["home", "work"]}),
+                json!("work"),
+                true,
+            ),
+            (
+                json!({"op": "in", "value": ["home", "mail"]}),
+                tags.clone(),
+                true,
+            ),
+            (json!({"op": "in", "value": ["home"]}), tags.clone(), false),
+            (
+                json!({"op": "starts_with", "value": "ma"}),
+                tags.clone(),
+                true,
+            ),
+            (
+                json!({"op": "starts_with", "value": "ail"}),
+                tags.clone(),
+                false,
+            ),
+            (
+                json!({"op": "glob", "value": "web/*"}),
+                json!("web/mail"),
+                true,
+            ),
+            (
+                json!({"op": "glob", "value": "web/*"}),
+                json!("ssh/web"),
+                false,
+            ),
+            (json!({"op": "exists", "value": true}), json!(""), true),
+        ];
+
+        for (op, value, expected) in cases {
+            assert_eq!(
+                operator(op.clone()).matches(Some(&value)),
+                expected,
+                "{op} on {value}"
+            );
+        }
+    }
+
+    #[test]
+    fn missing_fields_only_match_ne_and_not_exists() {
+        let cases = [
+            (json!({"op": "exists", "value": false}), true),
+            (json!({"op": "ne", "value": "work"}), true),
+            (json!({"op": "exists", "value": true}), false),
+            (json!({"op": "eq", "value": "work"}), false),
+            (json!({"op": "lt", "value": 3}), false),
+            (json!({"op": "in", "value": ["work"]}), false),
+        ];
+
+        for (op, expected) in cases {
+            assert_eq!(operator(op.clone()).matches(None), expected, "{op}");
+        }
+    }
+
+    #[test]
+    fn dates_compare_as_instants() {
+        let midnight = json!("2026-01-01T00:00:00Z");
+
+        // Earlier than midnight UTC, although it sorts later as a string.
+        let eleven_pm = json!("2026-01-01T01:00:00+02:00");
+
+        assert!(Operator::Lt(midnight.clone()).matches(Some(&eleven_pm)));
+        assert!(!Operator::Gt(midnight.clone()).matches(Some(&eleven_pm)));
+
+        // A bare date is midnight UTC of that day.
+        assert!(Operator::Eq(json!("2026-01-01")).matches(Some(&midnight)));
+        assert!(Operator::Gte(json!("2026-01-01")).matches(Some(&midnight)));
+        assert!(Operator::Lt(json!("2026-01-02")).matches(Some(&eleven_pm)));
+    }
+
+    #[test]
+    fn dates_compare_relative_to_now() {
+        let stale = json!((Utc::now() - Duration::days(100)).to_rfc3339());
+        let fresh = json!((Utc::now() - Duration::days(10)).to_rfc3339());
+        let older_than_90_days = Operator::Lt(json!("now-90d"));
+
+        assert!(older_than_90_days.matches(Some(&stale)));
+        assert!(!older_than_90_days.matches(Some(&fresh)));
+        assert!(Operator::Gt(json!("now-2w")).matches(Some(&fresh)));
+        assert!(Operator::Lt(json!("now+1d")).matches(Some(&fresh)));
+    }
+
+    #[test]
+    fn filters_combine_conditions_on_metadata() {
+        let meta = Metadata {
+            path: PathBuf::from("web/mail"),
+            template: BaseMetadata {
+                tags: Some(vec!["work".to_string(), "mail".to_string()]),
+                ..BaseMetadata::default()
+            },
+            ..Metadata::default()
+        };
+        let filter = |value: Value| {
+            serde_json::from_value::<Filter>(value)
+                .unwrap()
+                .matches(&meta)
+        };
+
+        assert!(filter(json!({"and": [
+            {"condition": {"field": "path", "op": "glob", "value": "web/*"}},
+            {"condition": {"field": "tags", "op": "in", "value": ["mail"]}},
+            {"not": {"condition": {
+                "field": "rotate_every", "op": "exists", "value": true
+            }}},
+        ]})));
+        assert!(!filter(json!({"and": [
+            {"condition": {"field": "path", "op": "glob", "value": "web/*"}},
+            {"condition": {"field": "tags", "op": "in", "value": ["home"]}},
+        ]})));
+        assert!(filter(json!({"or": [
+            {"condition": {"field": "category", "op": "eq", "value": "x"}},
+            {"condition": {
+                "field": "updated_at", "op": "gte", "value": "now-1h"
+            }},
+        ]})));
+    }
+
+    #[test]
+    fn total_order_ranks_types_and_puts_null_last() {
+        let mut values = vec![
+            json!(null),
+            json!("b"),
+            json!(2),
+            json!("2025-01-01T00:00:00Z"),
+            json!(true),
+            json!(1),
+            json!("a"),
+            json!(null),
+        ];
+
+        values.sort_by(total_order);
+
+        assert_eq!(
+            values,
+            [
+                json!(true),
+                json!(1),
+                json!(2),
+                json!("2025-01-01T00:00:00Z"),
+                json!("a"),
+                json!("b"),
+                json!(null),
+                json!(null),
+            ]
+        );
+    }
+}
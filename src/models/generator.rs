@@ -0,0 +1,420 @@
+use sequoia_openpgp::crypto::random;
+use serde::Deserialize;
+use zeroize::Zeroizing;
+
+use crate::{
+    error::{CryptoContext, Error, Result},
+    utils::memory::SecretString,
+};
+
+pub(crate) const WORDLIST: &str = include_str!("../../assets/wordlist.txt");
+
+const LOWER: &[u8] = b"abcdefghijklmnopqrstuvwxyz";
+const UPPER: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+const DIGITS: &[u8] = b"0123456789";
+const SYMBOLS: &[u8] = b"!\"#$%&'()*+,-./:;<=>?@[\\]^_`{|}~";
+const CONSONANTS: &[u8] = b"bcdfghjklmnprstvwz";
+const VOWELS: &[u8] = b"aeiou";
+
+const DEFAULT_LENGTH: usize = 24;
+const DEFAULT_WORDS: usize = 6;
+const MAX_LENGTH: usize = 1024;
+const MAX_WORDS: usize = 64;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GenerateMode {
+    #[default]
+    Random,
+    Pronounceable,
+    Diceware,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CharClass {
+    Lower,
+    Upper,
+    Digits,
+    Symbols,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct GenerateOptions {
+    #[serde(default)]
+    pub mode: GenerateMode,
+    pub length: Option<usize>,
+    pub classes: Option<Vec<CharClass>>,
+    #[serde(default)]
+    pub exclude: String,
+    pub words: Option<usize>,
+    pub separator: Option<String>,
+    #[serde(default)]
+    pub capitalize: bool,
+}
+
+impl CharClass {
+    const ALL: [CharClass; 4] = [
+        CharClass::Lower,
+        CharClass::Upper,
+        CharClass::Digits,
+        CharClass::Symbols,
+    ];
+
+    fn chars(self) -> &'static [u8] {
+        match self {
+            CharClass::Lower => LOWER,
+            CharClass::Upper => UPPER,
+            CharClass::Digits => DIGITS,
+            CharClass::Symbols => SYMBOLS,
+        }
+    }
+}
+
+/// Maps `value` onto `0..bound`. Values in the tail above the last multiple
+/// of `bound` are rejected, since keeping them would favour low indices.
+fn below(value: u64, bound: u64) -> Option<usize> {
+    // 2^64 mod `bound`, the size of that tail.
+    let tail = bound.wrapping_neg() % bound;
+
+    (value <= u64::MAX - tail).then(|| (value % bound) as usize)
+}
+
+fn random_below(bound: usize) -> Result<usize> {
+    loop {
+        // The raw bytes decide which character is picked.
+        let mut bytes = Zeroizing::new([0u8; 8]);
+
+        random(&mut *bytes).crypto_context("Failed to gather random bytes")?;
+
+        if let Some(index) = below(u64::from_le_bytes(*bytes), bound as u64) {
+            return Ok(index);
+        }
+    }
+}
+
+fn pick(chars: &[u8]) -> Result<u8> {
+    Ok(chars[random_below(chars.len())?])
+}
+
+fn shuffle(bytes: &mut [u8]) -> Result<()> {
+    for i in (1..bytes.len()).rev() {
+        bytes.swap(i, random_below(i + 1)?);
+    }
+
+    Ok(())
+}
+
+impl GenerateOptions {
+    fn classes(&self) -> Vec<CharClass> {
+        let mut classes = Vec::new();
+
+        for class in self.classes.as_deref().unwrap_or(&CharClass::ALL) {
+            if !classes.contains(class) {
+                classes.push(*class);
+            }
+        }
+
+        classes
+    }
+
+    fn allowed(&self, chars: &[u8]) -> Vec<u8> {
+        chars
+            .iter()
+            .copied()
+            .filter(|c| !self.exclude.as_bytes().contains(c))
+            .collect()
+    }
+
+    fn class_chars(&self, class: CharClass) -> Result<Vec<u8>> {
+        let chars = self.allowed(class.chars());
+
+        if chars.is_empty() {
+            return Err(Error::InvalidParams(format!(
+                "Exclusions leave no characters for class '{class:?}'"
+            )));
+        }
+
+        Ok(chars)
+    }
+
+    fn length(&self) -> Result<usize> {
+        let length = self.length.unwrap_or(DEFAULT_LENGTH);
+
+        if !(1..=MAX_LENGTH).contains(&length) {
+            return Err(Error::InvalidParams(format!(
+                "Length must be between 1 and {MAX_LENGTH}"
+            )));
+        }
+
+        Ok(length)
+    }
+
+    pub fn generate(&self) -> Result<SecretString> {
+        let mut bytes = match self.mode {
+            GenerateMode::Random => self.random(),
+            GenerateMode::Pronounceable => self.pronounceable(),
+            GenerateMode::Diceware => self.diceware(),
+        }?;
+
+        SecretString::from_utf8(std::mem::take(&mut *bytes)).ok_or_else(|| {
+            Error::Internal("Generated value is not valid UTF-8".to_string())
+        })
+    }
+
+    fn random(&self) -> Result<Zeroizing<Vec<u8>>> {
+        let length = self.length()?;
+        let classes = self.classes();
+
+        if classes.is_empty() {
+            return Err(Error::InvalidParams(
+                "At least one character class is required".to_string(),
+            ));
+        }
+
+        if length < classes.len() {
+            return Err(Error::InvalidParams(format!(
+                "Length must be at least {} to include every class",
+                classes.len()
+            )));
+        }
+
+        let mut pool = Vec::new();
+        let mut bytes = Zeroizing::new(Vec::with_capacity(length));
+
+        // One character from each class first, so every requested class is
+        // guaranteed to appear once the result is shuffled.
+        for class in &classes {
+            let chars = self.class_chars(*class)?;
+
+            bytes.push(pick(&chars)?);
+            pool.extend(chars);
+        }
+
+        while bytes.len() < length {
+            bytes.push(pick(&pool)?);
+        }
+
+        shuffle(&mut bytes)?;
+
+        Ok(bytes)
+    }
+
+    fn pronounceable(&self) -> Result<Zeroizing<Vec<u8>>> {
+        let length = self.length()?;
+        let classes = self.classes();
+        let consonants = self.allowed(CONSONANTS);
+        let vowels = self.allowed(VOWELS);
+
+        if consonants.is_empty() || vowels.is_empty() {
+            return Err(Error::InvalidParams(
+                "Exclusions leave no letters for pronounceable passwords"
+                    .to_string(),
+            ));
+        }
+
+        let extras: Vec<CharClass> = classes
+            .iter()
+            .copied()
+            .filter(|c| matches!(c, CharClass::Digits | CharClass::Symbols))
+            .collect();
+
+        if length <= extras.len() {
+            return Err(Error::InvalidParams(format!(
+                "Length must be at least {} to include every class",
+                extras.len() + 1
+            )));
+        }
+
+        let mut bytes = Zeroizing::new(Vec::with_capacity(length));
+        let mut vowel = random_below(2)? == 0;
+
+        while bytes.len() < length - extras.len() {
+            bytes.push(pick(if vowel { &vowels } else { &consonants })?);
+            vowel = !vowel;
+        }
+
+        if classes.contains(&CharClass::Upper) {
+            let index = random_below(bytes.len())?;
+            let upper = bytes[index].to_ascii_uppercase();
+
+            if !self.exclude.as_bytes().contains(&upper) {
+                bytes[index] = upper;
+            }
+        }
+
+        // Digits and symbols trail the syllables to keep them readable.
+        for class in extras {
+            bytes.push(pick(&self.class_chars(class)?)?);
+        }
+
+        Ok(bytes)
+    }
+
+    fn diceware(&self) -> Result<Zeroizing<Vec<u8>>> {
+        let count = self.words.unwrap_or(DEFAULT_WORDS);
+        let separator = self.separator.as_deref().unwrap_or("-");
+
+        if !(1..=MAX_WORDS).contains(&count) {
+            return Err(Error::InvalidParams(format!(
+                "Words must be between 1 and {MAX_WORDS}"
+            )));
+        }
+
+        let words: Vec<&str> = WORDLIST
+            .lines()
+            .filter(|word| {
+                !word.bytes().any(|c| self.exclude.as_bytes().contains(&c))
+            })
+            .collect();
+
+        if words.is_empty() {
+            return Err(Error::InvalidParams(
+                "Exclusions leave no words in the wordlist".to_string(),
+            ));
+        }
+
+        let longest = words.iter().map(|word| word.len()).max().unwrap_or(0);
+        // Reserving the worst case up front avoids leaving copies of partial
+        // passphrases behind in reallocated buffers.
+        let mut bytes = Zeroizing::new(Vec::with_capacity(
+            count * (longest + separator.len()),
+        ));
+
+        for i in 0..count {
+            if i > 0 {
+                bytes.extend_from_slice(separator.as_bytes());
+            }
+
+            let word = words[random_below(words.len())?].as_bytes();
+            let start = bytes.len();
+
+            bytes.extend_from_slice(word);
+
+            if self.capitalize {
+                bytes[start] = bytes[start].to_ascii_uppercase();
+            }
+        }
+
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    fn options(value: serde_json::Value) -> GenerateOptions {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn generate(value: serde_json::Value) -> Result<String> {
+        options(value)
+            .generate()
+            .map(|secret| secret.as_str().to_string())
+    }
+
+    #[test]
+    fn rejection_sampling_drops_the_biased_tail() {
+        let bound = WORDLIST.lines().count() as u64;
+        // Values below the last multiple of `bound` map onto every index
+        // equally often.
+        let zone = u64::MAX / bound * bound;
+
+        assert_eq!(below(0, bound), Some(0));
+        assert_eq!(below(bound + 2, bound), Some(2));
+        assert_eq!(below(zone - 1, bound), Some(bound as usize - 1));
+        assert_eq!(below(zone, bound), None);
+        assert_eq!(below(u64::MAX, bound), None);
+
+        // A power of two divides the range evenly and rejects nothing.
+        assert_eq!(below(u64::MAX, 8), Some(7));
+    }
+
+    #[test]
+    fn random_indices_cover_the_whole_range() {
+        let mut seen = [false; 6];
+
+        for _ in 0..600 {
+            seen[random_below(6).unwrap()] = true;
+        }
+
+        assert!(seen.iter().all(|&seen| seen), "{seen:?}");
+    }
+
+    #[test]
+    fn diceware_picks_whole_words_from_the_list() {
+        let words: HashSet<&str> = WORDLIST.lines().collect();
+        let passphrase = generate(serde_json::json!({
+            "mode": "diceware",
+            "words": 8,
+            "separator": " ",
+            "capitalize": true,
+        }))
+        .unwrap();
+        let picked: Vec<&str> = passphrase.split(' ').collect();
+
+        assert_eq!(words.len(), 7776);
+        assert_eq!(picked.len(), 8);
+
+        for word in picked {
+            assert!(word.starts_with(|c: char| c.is_ascii_uppercase()));
+            assert!(words.contains(word.to_lowercase().as_str()), "{word}");
+        }
+    }
+
+    #[test]
+    fn diceware_honours_exclusions_and_limits() {
+        let passphrase = generate(serde_json::json!({
+            "mode": "diceware",
+            "exclude": "e",
+        }))
+        .unwrap();
+
+        assert_eq!(passphrase.split('-').count(), DEFAULT_WORDS);
+        assert!(!passphrase.contains('e'));
+
+        for words in [0, MAX_WORDS + 1] {
+            assert!(matches!(
+                generate(serde_json::json!({
+                    "mode": "diceware",
+                    "words": words,
+                })),
+                Err(Error::InvalidParams(_))
+            ));
+        }
+
+        assert!(matches!(
+            generate(serde_json::json!({
+                "mode": "diceware",
+                "exclude": "abcdefghijklmnopqrstuvwxyz",
+            })),
+            Err(Error::InvalidParams(message))
+                if message == "Exclusions leave no words in the wordlist"
+        ));
+    }
+
+    #[test]
+    fn random_passwords_include_every_requested_class() {
+        let password = generate(serde_json::json!({
+            "length": 4,
+            "classes": ["lower", "upper", "digits", "symbols"],
+            "exclude": "0O1lI",
+        }))
+        .unwrap();
+
+        assert_eq!(password.len(), 4);
+        assert!(password.bytes().any(|c| c.is_ascii_lowercase()));
+        assert!(password.bytes().any(|c| c.is_ascii_uppercase()));
+        assert!(password.bytes().any(|c| c.is_ascii_digit()));
+        assert!(password.bytes().any(|c| c.is_ascii_punctuation()));
+        assert!(!password.contains(['0', 'O', '1', 'l', 'I']));
+
+        assert!(matches!(
+            generate(serde_json::json!({ "length": 3 })),
+            Err(Error::InvalidParams(_))
+        ));
+    }
+}
@@ -0,0 +1,595 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+use walkdir::WalkDir;
+
+use crate::{
+    error::{Error, IoContext, Result},
+    models::{
+        config::Config,
+        metadata::Metadata,
+        search::{Terms, index_terms},
+        secret::{load_metadata, metadata_path},
+    },
+    utils::{
+        cancel::CancellationToken,
+        checksum::{compute_checksum, compute_checksum_from_file},
+        fs::{secure_write, set_secure_dir_permissions},
+    },
+};
+
+const INDEX_FILE_NAME: &str = "index.json";
+const INDEX_VERSION: u32 = 3;
+
+/// Relative paths of the `.meta.toml` files under `prefix` in `metadata_dir`.
+fn metadata_files<'a>(
+    config: &'a Config,
+    prefix: &Path,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    WalkDir::new(config.metadata_dir.join(prefix))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            e.path()
+                .strip_prefix(&config.metadata_dir)
+                .ok()?
+                .to_str()?
+                .strip_suffix(".meta.toml")
+                .map(PathBuf::from)
+        })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    pub metadata: Metadata,
+    pub modified: SystemTime,
+    pub checksum: String,
+    #[serde(default)]
+    pub terms: Terms,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetadataIndex {
+    pub version: u32,
+    pub entries: BTreeMap<PathBuf, IndexEntry>,
+}
+
+impl MetadataIndex {
+    pub fn index_path(config: &Config) -> PathBuf {
+        config.base_dir.join(INDEX_FILE_NAME)
+    }
+
+    pub fn open(config: &Arc<Config>) -> Result<Self> {
+        match Self::load(config) {
+            Ok(Some(index)) => Ok(index),
+            Ok(None) => Self::rebuild(config, &CancellationToken::new()),
+            Err(e) => {
+                log::warn!("Discarding unreadable metadata index: {}", e);
+
+                Self::rebuild(config, &CancellationToken::new())
+            }
+        }
+    }
+
+    pub fn load(config: &Config) -> Result<Option<Self>> {
+        let index_path = Self::index_path(config);
+
+        if !index_path.exists() {
+            return Ok(None);
+        }
+
+        let text = fs::read_to_string(&index_path).io_context(format!(
+            "Failed to read metadata index {}",
+            index_path.display()
+        ))?;
+        let index: Self = serde_json::from_str(&text).map_err(|e| {
+            Error::Internal(format!("Failed to parse metadata index: {e}"))
+        })?;
+
+        if index.version != INDEX_VERSION {
+            return Ok(None);
+        }
+
+        Ok(Some(index))
+    }
+
+    pub fn save(&self, config: &Config) -> Result<()> {
+        let index_path = Self::index_path(config);
+        let temp_path = index_path.with_extension("json.tmp");
+        let text = serde_json::to_string(self).map_err(|e| {
+            Error::Internal(format!("Failed to serialize metadata index: {e}"))
+        })?;
+
+        if !config.base_dir.exists() {
+            fs::create_dir_all(&config.base_dir)
+                .and_then(|_| set_secure_dir_permissions(&config.base_dir))
+                .io_context("Failed to create base directory")?;
+        }
+
+        secure_write(&temp_path, text)
+            .io_context("Failed to write metadata index")?;
+        fs::rename(&temp_path, &index_path)
+            .io_context("Failed to replace metadata index")?;
+
+        Ok(())
+    }
+
+    pub fn invalidate(config: &Config) {
+        let index_path = Self::index_path(config);
+
+        if let Err(e) = fs::remove_file(&index_path)
+            && e.kind() != std::io::ErrorKind::NotFound
+        {
+            log::error!(
+                "Failed to remove stale metadata index {}: {}",
+                index_path.display(),
+                e
+            );
+        }
+    }
+
+    pub fn rebuild(
+        config: &Arc<Config>,
+        cancel: &CancellationToken,
+    ) -> Result<Self> {
+        let mut index = Self {
+            version: INDEX_VERSION,
+            entries: BTreeMap::new(),
+        };
+
+        for relative_path in metadata_files(config, Path::new("")) {
+            cancel.check()?;
+
+            if let Err(e) = index.refresh(config, &relative_path) {
+                log::warn!(
+                    "Skipping '{}' while indexing: {}",
+                    relative_path.display(),
+                    e
+                );
+            }
+        }
+
+        index.save(config)?;
+
+        log::info!("Rebuilt metadata index with {} entries", index.len());
+
+        Ok(index)
+    }
+
+    pub fn refresh(
+        &mut self,
+        config: &Arc<Config>,
+        relative_path: &Path,
+    ) -> Result<()> {
+        let metadata_path = metadata_path(config, relative_path);
+
+        if !metadata_path.exists() {
+            self.entries.remove(relative_path);
+
+            return Ok(());
+        }
+
+        let (metadata, text) = match load_metadata(config, relative_path) {
+            Ok(read) => read,
+            Err(e) => {
+                self.entries.remove(relative_path);
+
+                return Err(e);
+            }
+        };
+        let modified = fs::metadata(&metadata_path)
+            .and_then(|m| m.modified())
+            .io_context(format!(
+                "Failed to stat metadata {}",
+                metadata_path.display()
+            ))?;
+
+        self.entries.insert(
+            relative_path.to_path_buf(),
+            IndexEntry {
+                terms: index_terms(relative_path, &metadata),
+                metadata,
+                modified,
+                checksum: compute_checksum(&text),
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Re-reads entries whose metadata file was removed or rewritten since
+    /// it was indexed, and indexes metadata files it has not seen yet. A
+    /// changed mtime alone is only trusted once the file's checksum confirms
+    /// the content is still the same. Returns whether any entry changed; the
+    /// index is only copied when one did.
+    fn revalidate(index: &mut Arc<Self>, config: &Arc<Config>) -> bool {
+        let mut stale: Vec<PathBuf> = metadata_files(config, Path::new(""))
+            .filter(|relative_path| !index.entries.contains_key(relative_path))
+            .collect();
+        let mut touched = Vec::new();
+
+        for (relative_path, entry) in &index.entries {
+            let metadata_path = metadata_path(config, relative_path);
+            let Ok(modified) =
+                fs::metadata(&metadata_path).and_then(|m| m.modified())
+            else {
+                stale.push(relative_path.clone());
+                continue;
+            };
+
+            if modified == entry.modified {
+                continue;
+            }
+
+            match fs::read_to_string(&metadata_path) {
+                Ok(text) if compute_checksum(&text) == entry.checksum => {
+                    touched.push((relative_path.clone(), modified));
+                }
+                _ => stale.push(relative_path.clone()),
+            }
+        }
+
+        if stale.is_empty() && touched.is_empty() {
+            return false;
+        }
+
+        let index = Arc::make_mut(index);
+
+        for (relative_path, modified) in &touched {
+            if let Some(entry) = index.entries.get_mut(relative_path) {
+                entry.modified = *modified;
+            }
+        }
+
+        for relative_path in &stale {
+            log::info!(
+                "Metadata '{}' changed outside passd, re-indexing it",
+                relative_path.display()
+            );
+
+            if let Err(e) = index.refresh(config, relative_path) {
+                log::warn!(
+                    "Dropping '{}' from the metadata index: {}",
+                    relative_path.display(),
+                    e
+                );
+            }
+        }
+
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+/// The metadata index kept in memory for the lifetime of the daemon. Reads
+/// hand out a snapshot, so a long scan never blocks writers; writes update
+/// the cached index in place and then persist it.
+///
+/// Without a watcher every read has to check the files for changes made
+/// outside passd. While one runs it keeps the index current, and reads only
+/// check the files again when it may have missed changes.
+#[derive(Debug, Default)]
+pub struct SharedIndex {
+    cached: Mutex<Option<Arc<MetadataIndex>>>,
+    watched: AtomicBool,
+    verified: AtomicBool,
+}
+
+impl SharedIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn loaded<'a>(
+        cached: &'a mut Option<Arc<MetadataIndex>>,
+        config: &Arc<Config>,
+    ) -> Result<&'a mut Arc<MetadataIndex>> {
+        if cached.is_none() {
+            *cached = Some(Arc::new(MetadataIndex::open(config)?));
+        }
+
+        Ok(cached.as_mut().expect("index was loaded above"))
+    }
+
+    pub fn snapshot(&self, config: &Arc<Config>) -> Result<Arc<MetadataIndex>> {
+        let mut cached =
+            self.cached.lock().unwrap_or_else(PoisonError::into_inner);
+        let index = Self::loaded(&mut cached, config)?;
+        let trusted = self.watched.load(Ordering::Acquire)
+            && self.verified.swap(true, Ordering::AcqRel);
+
+        if !trusted
+            && MetadataIndex::revalidate(index, config)
+            && let Err(e) = index.save(config)
+        {
+            log::warn!("Failed to save metadata index: {}", e);
+        }
+
+        Ok(Arc::clone(index))
+    }
+
+    /// Records whether a watcher keeps the index current. Files may have
+    /// changed before it started, so the next read checks them either way.
+    pub fn set_watched(&self, watched: bool) {
+        self.verified.store(false, Ordering::Release);
+        self.watched.store(watched, Ordering::Release);
+    }
+
+    /// Makes the next read check the files again, for when the watcher may
+    /// have missed changes.
+    pub fn mark_unverified(&self) {
+        self.verified.store(false, Ordering::Release);
+    }
+
+    /// The index as loaded, without looking at the files again.
+    fn cached(&self) -> Option<Arc<MetadataIndex>> {
+        self.cached
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Whether the index already reflects the files of `relative_path`: its
+    /// metadata is the text that was indexed and its secret matches the
+    /// indexed `checksum_main`. This holds right after passd writes a secret
+    /// itself, so the watcher can tell its own writes from outside changes.
+    pub fn is_current(&self, config: &Config, relative_path: &Path) -> bool {
+        let Some(index) = self.cached() else {
+            return false;
+        };
+        let metadata_path = metadata_path(config, relative_path);
+        let Some(entry) = index.entries.get(relative_path) else {
+            return !metadata_path.exists();
+        };
+        let secret_path =
+            config.secrets_dir.join(relative_path).with_extension("pgp");
+
+        fs::read_to_string(&metadata_path)
+            .is_ok_and(|text| compute_checksum(&text) == entry.checksum)
+            && (!secret_path.exists()
+                || compute_checksum_from_file(&secret_path).is_ok_and(
+                    |checksum| checksum == entry.metadata.checksum_main,
+                ))
+    }
+
+    pub fn update(&self, config: &Arc<Config>, relative_paths: &[&Path]) {
+        let mut cached =
+            self.cached.lock().unwrap_or_else(PoisonError::into_inner);
+        let result = Self::loaded(&mut cached, config).and_then(|index| {
+            let index = Arc::make_mut(index);
+
+            for relative_path in relative_paths {
+                index.refresh(config, relative_path)?;
+            }
+
+            index.save(config)
+        });
+
+        if let Err(e) = result {
+            log::warn!("Failed to update metadata index: {}", e);
+
+            *cached = None;
+            MetadataIndex::invalidate(config);
+        }
+    }
+
+    /// Indexed paths under `prefix` plus the metadata files currently found
+    /// below it, so a directory moved or removed as a whole can be refreshed.
+    pub fn paths_under(
+        &self,
+        config: &Arc<Config>,
+        prefix: &Path,
+    ) -> BTreeSet<PathBuf> {
+        let mut paths: BTreeSet<PathBuf> =
+            metadata_files(config, prefix).collect();
+
+        if let Some(index) = self.cached() {
+            paths.extend(
+                index
+                    .entries
+                    .keys()
+                    .filter(|path| path.starts_with(prefix))
+                    .cloned(),
+            );
+        }
+
+        paths
+    }
+
+    pub fn rebuild(
+        &self,
+        config: &Arc<Config>,
+        cancel: &CancellationToken,
+    ) -> Result<usize> {
+        let index = MetadataIndex::rebuild(config, cancel)?;
+        let len = index.len();
+
+        *self.cached.lock().unwrap_or_else(PoisonError::into_inner) =
+            Some(Arc::new(index));
+
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::models::metadata::BaseMetadata;
+
+    fn test_config(dir: &Path) -> Arc<Config> {
+        Arc::new(Config {
+            base_dir: dir.to_path_buf(),
+            secrets_dir: dir.join("secrets"),
+            metadata_dir: dir.join(".metadata"),
+            keys_dir: dir.join(".keys"),
+            ..Config::default()
+        })
+    }
+
+    fn write_metadata(config: &Config, relative_path: &str, description: &str) {
+        let metadata = Metadata {
+            path: PathBuf::from(relative_path),
+            template: BaseMetadata {
+                description: Some(description.to_string()),
+                ..BaseMetadata::default()
+            },
+            ..Metadata::default()
+        };
+        let path = metadata_path(config, Path::new(relative_path));
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, toml::to_string_pretty(&metadata).unwrap()).unwrap();
+
+        // Make sure the rewrite is visible even on coarse mtime clocks.
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+    }
+
+    fn description(
+        index: &MetadataIndex,
+        relative_path: &str,
+    ) -> Option<String> {
+        index
+            .entries
+            .get(Path::new(relative_path))?
+            .metadata
+            .template
+            .description
+            .clone()
+    }
+
+    #[test]
+    fn snapshot_revalidates_entries_changed_outside_passd() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let shared = SharedIndex::new();
+
+        write_metadata(&config, "web/mail", "before");
+        write_metadata(&config, "web/bank", "kept");
+
+        let index = shared.snapshot(&config).unwrap();
+
+        assert_eq!(description(&index, "web/mail").as_deref(), Some("before"));
+        assert_eq!(index.len(), 2);
+
+        write_metadata(&config, "web/mail", "after");
+        fs::remove_file(metadata_path(&config, Path::new("web/bank"))).unwrap();
+
+        let index = shared.snapshot(&config).unwrap();
+
+        assert_eq!(description(&index, "web/mail").as_deref(), Some("after"));
+        assert_eq!(index.len(), 1);
+
+        let saved = MetadataIndex::load(&config).unwrap().unwrap();
+
+        assert_eq!(description(&saved, "web/mail").as_deref(), Some("after"));
+        assert_eq!(saved.len(), 1);
+    }
+
+    #[test]
+    fn snapshot_keeps_entries_whose_content_is_unchanged() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let shared = SharedIndex::new();
+
+        write_metadata(&config, "web/mail", "same");
+
+        let before = shared.snapshot(&config).unwrap();
+        let path = metadata_path(&config, Path::new("web/mail"));
+        let touched = SystemTime::now() + Duration::from_secs(60);
+
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(touched)
+            .unwrap();
+
+        let after = shared.snapshot(&config).unwrap();
+        let entry = &after.entries[Path::new("web/mail")];
+
+        assert_eq!(entry.modified, touched);
+        assert_eq!(
+            entry.checksum,
+            before.entries[Path::new("web/mail")].checksum
+        );
+
+        // Nothing changed, so the next read shares the cached index.
+        assert!(Arc::ptr_eq(&after, &shared.snapshot(&config).unwrap()));
+    }
+
+    #[test]
+    fn snapshot_indexes_files_added_outside_passd() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let shared = SharedIndex::new();
+
+        write_metadata(&config, "web/mail", "first");
+
+        assert_eq!(shared.snapshot(&config).unwrap().len(), 1);
+
+        write_metadata(&config, "web/bank", "added");
+
+        let index = shared.snapshot(&config).unwrap();
+
+        assert_eq!(description(&index, "web/bank").as_deref(), Some("added"));
+        assert_eq!(index.len(), 2);
+    }
+
+    #[test]
+    fn watched_snapshots_only_check_files_when_unverified() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let shared = SharedIndex::new();
+
+        write_metadata(&config, "web/mail", "before");
+        shared.snapshot(&config).unwrap();
+
+        // Changed while nothing watched, so the first watched read sees it.
+        write_metadata(&config, "web/mail", "during");
+        shared.set_watched(true);
+
+        let index = shared.snapshot(&config).unwrap();
+
+        assert_eq!(description(&index, "web/mail").as_deref(), Some("during"));
+
+        // From here on the watcher is trusted to report changes.
+        write_metadata(&config, "web/mail", "after");
+        write_metadata(&config, "web/bank", "added");
+
+        let index = shared.snapshot(&config).unwrap();
+
+        assert_eq!(description(&index, "web/mail").as_deref(), Some("during"));
+        assert_eq!(index.len(), 1);
+
+        shared.mark_unverified();
+
+        let index = shared.snapshot(&config).unwrap();
+
+        assert_eq!(description(&index, "web/mail").as_deref(), Some("after"));
+        assert_eq!(index.len(), 2);
+    }
+}
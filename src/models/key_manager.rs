@@ -1,9 +1,13 @@
-use std::sync::Arc;
+use std::{fs, sync::Arc};
 
-use anyhow::{Context, Result};
-use sequoia_openpgp::Cert;
+use sequoia_openpgp::{
+    Cert, Fingerprint, KeyID, cert::CertParser, parse::Parse,
+};
 
-use crate::models::config::Config;
+use crate::{
+    error::{CryptoContext, Error, IoContext, Result},
+    models::config::Config,
+};
 
 #[derive(Debug)]
 pub struct KeyManager {
@@ -15,10 +19,67 @@ impl KeyManager {
         Self { config }
     }
 
-    pub fn get_secret_cert(&self, fingerprint: &str) -> Result<Option<Cert>> {
-        let cert = Cer::from_bytes(key_data.as_bytes())
-            .context("Failed to parse secret key into certificate")?;
+    fn certs(&self) -> Result<Vec<Cert>> {
+        let keys_dir = &self.config.keys_dir;
+        let mut certs = Vec::new();
+
+        if !keys_dir.exists() {
+            return Ok(certs);
+        }
+
+        for entry in fs::read_dir(keys_dir)
+            .io_context(format!(
+                "Failed to read keys directory {}",
+                keys_dir.display()
+            ))?
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_file())
+        {
+            let path = entry.path();
+            let parser = match CertParser::from_file(&path) {
+                Ok(parser) => parser,
+                Err(e) => {
+                    log::warn!("Skipping key file {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+
+            certs.extend(parser.filter_map(|cert| cert.ok()));
+        }
+
+        Ok(certs)
+    }
+
+    fn find_cert(&self, fingerprint: &str) -> Result<Cert> {
+        let fingerprint = Fingerprint::from_hex(fingerprint)
+            .crypto_context(format!("Invalid fingerprint '{fingerprint}'"))?;
+
+        self.certs()?
+            .into_iter()
+            .find(|cert| {
+                cert.keys().any(|ka| ka.key().fingerprint() == fingerprint)
+            })
+            .ok_or_else(|| Error::KeyNotFound(fingerprint.to_hex()))
+    }
+
+    pub fn get_public_cert(&self, fingerprint: &str) -> Result<Cert> {
+        Ok(self.find_cert(fingerprint)?.strip_secret_key_material())
+    }
+
+    pub fn get_secret_cert(&self, fingerprint: &str) -> Result<Cert> {
+        let cert = self.find_cert(fingerprint)?;
+
+        if !cert.is_tsk() {
+            return Err(Error::KeyNotFound(cert.fingerprint().to_hex()));
+        }
 
         Ok(cert)
     }
+
+    pub fn find_cert_by_keyid(&self, keyid: &KeyID) -> Option<Cert> {
+        self.certs()
+            .ok()?
+            .into_iter()
+            .find(|cert| cert.keys().any(|ka| ka.key().keyid() == *keyid))
+    }
 }
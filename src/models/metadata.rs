@@ -1,10 +1,15 @@
 use std::{collections::HashMap, path::PathBuf};
 
-use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, to_value};
-use toml::{self, Value as TomlValue};
+use toml::{self, Table, Value as TomlValue};
+
+use crate::{
+    error::{Error, Result},
+    models::{content::ContentFormat, migration::METADATA_VERSION},
+    utils::{checksum::compute_checksum, time::parse_duration},
+};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BaseMetadata {
@@ -13,20 +18,60 @@ pub struct BaseMetadata {
     pub tags: Option<Vec<String>>,
     pub description: Option<String>,
     pub attachments: Option<Vec<String>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub rotate_every: Option<String>,
+    pub content_format: Option<ContentFormat>,
     #[serde(flatten)]
     pub extra: Option<HashMap<String, TomlValue>>,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase", tag = "op")]
+pub enum MetadataOp {
+    Set { field: String, value: Value },
+    Unset { field: String },
+    Append { field: String, value: Value },
+    Remove { field: String, value: Value },
+}
+
+// `content_format` is only changed together with content that is checked
+// against it, and stays readable so fields can be found without a password.
+const MANAGED_FIELDS: [&str; 10] = [
+    "content_format",
+    "schema_version",
+    "path",
+    "modifications",
+    "created_at",
+    "updated_at",
+    "rotated_at",
+    "checksum_main",
+    "checksum_meta",
+    "encrypted",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeadlineKind {
+    Expiry,
+    Rotation,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Metadata {
     #[serde(flatten)]
     pub template: BaseMetadata,
+    #[serde(default)]
+    pub schema_version: u32,
     pub path: PathBuf,
     pub modifications: u32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotated_at: Option<DateTime<Utc>>,
     pub checksum_main: String,
     pub checksum_meta: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted: Option<String>,
 }
 
 impl Default for BaseMetadata {
@@ -37,6 +82,9 @@ impl Default for BaseMetadata {
             tags: Some(Vec::new()),
             description: Some(String::new()),
             attachments: Some(Vec::new()),
+            expires_at: None,
+            rotate_every: None,
+            content_format: None,
             extra: None,
         }
     }
@@ -49,11 +97,14 @@ impl Default for Metadata {
         Self {
             path: PathBuf::new(),
             template: BaseMetadata::default(),
+            schema_version: METADATA_VERSION,
             modifications: 0,
             created_at: now,
             updated_at: now,
+            rotated_at: None,
             checksum_main: String::new(),
             checksum_meta: String::new(),
+            encrypted: None,
         }
     }
 }
@@ -73,24 +124,199 @@ impl From<BaseMetadata> for Metadata {
     }
 }
 
+impl BaseMetadata {
+    pub fn with_template(self, template: &BaseMetadata) -> Self {
+        let mut tags = template.tags.clone().unwrap_or_default();
+
+        for tag in self.tags.into_iter().flatten() {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+
+        let extra = match (template.extra.clone(), self.extra) {
+            (Some(mut extra), Some(overrides)) => {
+                extra.extend(overrides);
+                Some(extra)
+            }
+            (extra, overrides) => overrides.or(extra),
+        };
+
+        Self {
+            r#type: self.r#type.or_else(|| template.r#type.clone()),
+            category: self.category.or_else(|| template.category.clone()),
+            tags: Some(tags),
+            description: self
+                .description
+                .or_else(|| template.description.clone()),
+            attachments: self
+                .attachments
+                .or_else(|| template.attachments.clone()),
+            expires_at: self.expires_at.or(template.expires_at),
+            rotate_every: self
+                .rotate_every
+                .or_else(|| template.rotate_every.clone()),
+            content_format: self.content_format.or(template.content_format),
+            extra,
+        }
+    }
+
+    pub fn rotation_interval(&self) -> Option<Duration> {
+        self.rotate_every.as_deref().and_then(parse_duration)
+    }
+}
+
 impl Metadata {
     pub fn to_base(&self) -> BaseMetadata {
         self.template.clone()
     }
 
-    pub fn merge(&self, other: &Metadata) -> Result<Self> {
-        let mut self_value = toml::Value::try_from(self.clone())
-            .context("Failed to convert current metadata to TOML value")?;
+    pub fn content_format(&self) -> ContentFormat {
+        self.template.content_format.unwrap_or_default()
+    }
+
+    pub fn rotation_due(&self) -> Option<DateTime<Utc>> {
+        let rotated_at = self.rotated_at.unwrap_or(self.created_at);
+
+        rotated_at.checked_add_signed(self.template.rotation_interval()?)
+    }
+
+    pub fn deadline(&self) -> Option<(DeadlineKind, DateTime<Utc>)> {
+        [
+            self.template
+                .expires_at
+                .map(|at| (DeadlineKind::Expiry, at)),
+            self.rotation_due().map(|at| (DeadlineKind::Rotation, at)),
+        ]
+        .into_iter()
+        .flatten()
+        .min_by_key(|(_, at)| *at)
+    }
+
+    pub fn compute_checksum_meta(&self) -> Result<String> {
+        let unsigned = Metadata {
+            checksum_meta: String::new(),
+            ..self.clone()
+        };
+        let text = toml::to_string_pretty(&unsigned).map_err(|e| {
+            Error::Internal(format!("Failed to serialize metadata: {e}"))
+        })?;
+
+        Ok(compute_checksum(&text))
+    }
+
+    fn to_toml(&self) -> Result<TomlValue> {
+        TomlValue::try_from(self).map_err(|e| {
+            Error::Internal(format!(
+                "Failed to convert metadata to TOML value: {e}"
+            ))
+        })
+    }
 
-        let other_value = toml::Value::try_from(other.clone())
-            .context("Failed to convert other metadata to TOML value")?;
+    fn with_toml(&self, value: TomlValue) -> Result<Self> {
+        value.try_into().map_err(|e| Error::InvalidMetadata {
+            path: self.path.clone(),
+            reason: format!("Failed to deserialize merged metadata: {e}"),
+        })
+    }
+
+    pub fn merge(&self, other: &BaseMetadata) -> Result<Self> {
+        let mut self_value = self.to_toml()?;
+        let other_value = TomlValue::try_from(other).map_err(|e| {
+            Error::Internal(format!(
+                "Failed to convert other metadata to TOML value: {e}"
+            ))
+        })?;
 
         merge_toml(&mut self_value, &other_value);
 
-        let merged: Metadata = toml::from_str(&self_value.to_string())
-            .context("Failed to deserialize merged metadata from TOML")?;
+        self.with_toml(self_value)
+    }
+
+    pub fn apply(&self, ops: &[MetadataOp]) -> Result<Self> {
+        let mut value = self.to_toml()?;
+
+        for op in ops {
+            let field = op.field();
+            let (parent, key) = match field.rsplit_once('.') {
+                Some((parent, key)) => (Some(parent), key),
+                None => (None, field),
+            };
+
+            if key.is_empty()
+                || MANAGED_FIELDS
+                    .contains(&field.split('.').next().unwrap_or(field))
+            {
+                return Err(Error::InvalidParams(format!(
+                    "Metadata field '{field}' cannot be modified"
+                )));
+            }
+
+            let table = match parent {
+                Some(parent) => table_at(&mut value, parent, op.creates()),
+                None => value.as_table_mut(),
+            };
+            let Some(table) = table else {
+                if op.creates() {
+                    return Err(Error::InvalidParams(format!(
+                        "Metadata field '{field}' is not inside a table"
+                    )));
+                }
+
+                continue;
+            };
+
+            op.apply(table, key)?;
+        }
+
+        self.with_toml(value)
+    }
+
+    pub fn seal(&self, fields: &[String]) -> Result<(Self, Option<Table>)> {
+        let mut value = self.to_toml()?;
+        let table = value.as_table_mut().ok_or_else(|| {
+            Error::Internal("Metadata is not a table".to_string())
+        })?;
+        let keys: Vec<String> = if fields.iter().any(|f| f == "*") {
+            table
+                .keys()
+                .filter(|key| !MANAGED_FIELDS.contains(&key.as_str()))
+                .cloned()
+                .collect()
+        } else {
+            fields
+                .iter()
+                .filter(|key| !MANAGED_FIELDS.contains(&key.as_str()))
+                .cloned()
+                .collect()
+        };
+        let sealed: Table = keys
+            .iter()
+            .filter_map(|key| table.remove_entry(key))
+            .collect();
+
+        table.remove("encrypted");
+
+        let plain = self.with_toml(value)?;
 
-        Ok(merged)
+        Ok((plain, (!sealed.is_empty()).then_some(sealed)))
+    }
+
+    pub fn unseal(&self, sealed: Table) -> Result<Self> {
+        let mut value = self.to_toml()?;
+        let table = value.as_table_mut().ok_or_else(|| {
+            Error::Internal("Metadata is not a table".to_string())
+        })?;
+
+        table.remove("encrypted");
+
+        for (key, field) in sealed {
+            if !MANAGED_FIELDS.contains(&key.as_str()) {
+                table.insert(key, field);
+            }
+        }
+
+        self.with_toml(value)
     }
 
     pub fn get_field(
@@ -115,15 +341,96 @@ fn merge_toml(base: &mut TomlValue, other: &TomlValue) {
                 }
             }
         }
-        (TomlValue::Array(base_array), TomlValue::Array(other_array)) => {
-            base_array.extend(other_array.clone());
-        }
         (base_val, other_val) => {
             *base_val = other_val.clone();
         }
     }
 }
 
+impl MetadataOp {
+    fn field(&self) -> &str {
+        match self {
+            MetadataOp::Set { field, .. }
+            | MetadataOp::Unset { field }
+            | MetadataOp::Append { field, .. }
+            | MetadataOp::Remove { field, .. } => field,
+        }
+    }
+
+    fn creates(&self) -> bool {
+        matches!(self, MetadataOp::Set { .. } | MetadataOp::Append { .. })
+    }
+
+    fn apply(&self, table: &mut Table, key: &str) -> Result<()> {
+        match self {
+            MetadataOp::Set { value, .. } => {
+                table.insert(key.to_string(), to_toml_value(value)?);
+            }
+            MetadataOp::Unset { .. } => {
+                table.remove(key);
+            }
+            MetadataOp::Append { field, value } => {
+                let items = table
+                    .entry(key)
+                    .or_insert_with(|| TomlValue::Array(Vec::new()))
+                    .as_array_mut()
+                    .ok_or_else(|| not_an_array(field))?;
+
+                for item in to_toml_items(value)? {
+                    if !items.contains(&item) {
+                        items.push(item);
+                    }
+                }
+            }
+            MetadataOp::Remove { field, value } => {
+                let Some(existing) = table.get_mut(key) else {
+                    return Ok(());
+                };
+                let items = existing
+                    .as_array_mut()
+                    .ok_or_else(|| not_an_array(field))?;
+                let removed = to_toml_items(value)?;
+
+                items.retain(|item| !removed.contains(item));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn not_an_array(field: &str) -> Error {
+    Error::InvalidParams(format!("Metadata field '{field}' is not an array"))
+}
+
+fn to_toml_value(value: &Value) -> Result<TomlValue> {
+    TomlValue::try_from(value).map_err(|e| {
+        Error::InvalidParams(format!("Value cannot be stored in metadata: {e}"))
+    })
+}
+
+fn to_toml_items(value: &Value) -> Result<Vec<TomlValue>> {
+    match value {
+        Value::Array(items) => items.iter().map(to_toml_value).collect(),
+        value => Ok(vec![to_toml_value(value)?]),
+    }
+}
+
+fn table_at<'a>(
+    value: &'a mut TomlValue,
+    path: &str,
+    create: bool,
+) -> Option<&'a mut Table> {
+    path.split('.')
+        .try_fold(value.as_table_mut()?, |table, key| {
+            if create && !table.contains_key(key) {
+                table.insert(key.to_string(), TomlValue::Table(Table::new()));
+            }
+
+            table.get_mut(key)?.as_table_mut()
+        })
+}
+
 fn get_nested_field(value: &Value, path: &str) -> Option<Value> {
     let parts: Vec<&str> = path.split('.').collect();
     let mut current = value;
@@ -139,3 +446,209 @@ fn get_nested_field(value: &Value, path: &str) -> Option<Value> {
 
     Some(current.clone())
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn base(text: &str) -> BaseMetadata {
+        toml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn caller_metadata_is_merged_over_the_template() {
+        let template = base(
+            r#"
+            category = "accounts"
+            tags = ["login"]
+            description = "Website login"
+            rotate_every = "90d"
+            url = ""
+            username = ""
+            "#,
+        );
+        let metadata = base(
+            r#"
+            type = "login"
+            tags = ["work", "login"]
+            username = "alice"
+            "#,
+        )
+        .with_template(&template);
+        let extra = metadata.extra.unwrap();
+
+        assert_eq!(metadata.r#type.as_deref(), Some("login"));
+        assert_eq!(metadata.category.as_deref(), Some("accounts"));
+        assert_eq!(metadata.tags.unwrap(), ["login", "work"]);
+        assert_eq!(metadata.description.as_deref(), Some("Website login"));
+        assert_eq!(metadata.rotate_every.as_deref(), Some("90d"));
+        assert_eq!(extra["url"].as_str(), Some(""));
+        assert_eq!(extra["username"].as_str(), Some("alice"));
+    }
+
+    #[test]
+    fn given_fields_replace_the_templates() {
+        let template = base(
+            r#"
+            category = "accounts"
+            description = "Website login"
+            "#,
+        );
+        let metadata = base(
+            r#"
+            category = "work"
+            description = "Mail"
+            "#,
+        )
+        .with_template(&template);
+
+        assert_eq!(metadata.category.as_deref(), Some("work"));
+        assert_eq!(metadata.description.as_deref(), Some("Mail"));
+        assert_eq!(metadata.tags.unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn deadline_is_the_earlier_of_expiry_and_rotation() {
+        let created_at: DateTime<Utc> = "2026-01-01T00:00:00Z".parse().unwrap();
+        let at = |text: &str| text.parse::<DateTime<Utc>>().unwrap();
+        let mut metadata = Metadata {
+            template: base(r#"rotate_every = "90d""#),
+            created_at,
+            ..Metadata::default()
+        };
+
+        // Never rotated, so the interval counts from creation.
+        assert_eq!(
+            metadata.deadline(),
+            Some((DeadlineKind::Rotation, at("2026-04-01T00:00:00Z")))
+        );
+
+        metadata.rotated_at = Some(at("2026-03-01T00:00:00Z"));
+
+        assert_eq!(
+            metadata.deadline(),
+            Some((DeadlineKind::Rotation, at("2026-05-30T00:00:00Z")))
+        );
+
+        metadata.template.expires_at = Some(at("2026-05-01T00:00:00Z"));
+
+        assert_eq!(
+            metadata.deadline(),
+            Some((DeadlineKind::Expiry, at("2026-05-01T00:00:00Z")))
+        );
+
+        metadata.template.expires_at = None;
+        metadata.template.rotate_every = Some("soon".to_string());
+
+        assert_eq!(metadata.deadline(), None);
+    }
+
+    fn metadata(text: &str) -> Metadata {
+        Metadata {
+            template: base(text),
+            ..Metadata::default()
+        }
+    }
+
+    fn ops(value: Value) -> Vec<MetadataOp> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn field(metadata: &Metadata, field: &str) -> Option<Value> {
+        metadata.get_field(field).unwrap()
+    }
+
+    #[test]
+    fn merging_replaces_arrays_instead_of_appending() {
+        let metadata = metadata(r#"tags = ["prod", "web"]"#);
+        let update = base(r#"tags = ["prod"]"#);
+        let once = metadata.merge(&update).unwrap();
+        let twice = once.merge(&update).unwrap();
+
+        assert_eq!(once.template.tags.unwrap(), ["prod"]);
+        assert_eq!(twice.template.tags.unwrap(), ["prod"]);
+    }
+
+    #[test]
+    fn operations_replace_append_remove_and_unset() {
+        let metadata = metadata(
+            r#"
+            tags = ["old", "web"]
+            url = "https://example.com"
+            "#,
+        );
+        let ops = ops(json!([
+            { "op": "append", "field": "tags", "value": ["prod", "web"] },
+            { "op": "remove", "field": "tags", "value": "old" },
+            { "op": "set", "field": "account.login", "value": "alice" },
+            { "op": "set", "field": "description", "value": "Mail" },
+            { "op": "unset", "field": "url" },
+        ]));
+        let updated = metadata.apply(&ops).unwrap();
+
+        assert_eq!(field(&updated, "tags"), Some(json!(["web", "prod"])));
+        assert_eq!(
+            field(&updated, "account.login"),
+            Some(Value::from("alice"))
+        );
+        assert_eq!(field(&updated, "description"), Some(Value::from("Mail")));
+        assert_eq!(field(&updated, "url"), None);
+
+        // Applying the same edits again changes nothing.
+        let again = updated.apply(&ops).unwrap();
+
+        assert_eq!(to_value(&again).unwrap(), to_value(&updated).unwrap());
+    }
+
+    #[test]
+    fn operations_on_missing_fields_only_create_when_adding() {
+        let metadata = metadata("");
+        let updated = metadata
+            .apply(&ops(json!([
+                { "op": "unset", "field": "url" },
+                { "op": "remove", "field": "scopes", "value": "read" },
+                { "op": "unset", "field": "account.login" },
+                { "op": "append", "field": "scopes", "value": "read" },
+            ])))
+            .unwrap();
+
+        assert_eq!(field(&updated, "url"), None);
+        assert_eq!(field(&updated, "account"), None);
+        assert_eq!(field(&updated, "scopes"), Some(json!(["read"])));
+    }
+
+    #[test]
+    fn invalid_operations_are_rejected() {
+        let metadata = metadata(r#"description = "Mail""#);
+        let rejected = |value: Value| match metadata.apply(&ops(json!([value])))
+        {
+            Err(Error::InvalidParams(message)) => message,
+            other => panic!("Expected invalid params, got {other:?}"),
+        };
+
+        assert_eq!(
+            rejected(json!({
+                "op": "set", "field": "path", "value": "elsewhere"
+            })),
+            "Metadata field 'path' cannot be modified"
+        );
+        assert_eq!(
+            rejected(json!({ "op": "unset", "field": "created_at" })),
+            "Metadata field 'created_at' cannot be modified"
+        );
+        assert_eq!(
+            rejected(json!({
+                "op": "append", "field": "description", "value": "x"
+            })),
+            "Metadata field 'description' is not an array"
+        );
+        assert_eq!(
+            rejected(json!({
+                "op": "set", "field": "description.text", "value": "x"
+            })),
+            "Metadata field 'description.text' is not inside a table"
+        );
+    }
+}
@@ -0,0 +1,50 @@
+use toml::{Table, Value as TomlValue};
+
+pub const METADATA_VERSION: u32 = 1;
+pub const VERSION_FIELD: &str = "schema_version";
+
+type Migration = fn(&mut Table);
+
+// MIGRATIONS[n] upgrades a metadata table from version n to n + 1.
+const MIGRATIONS: [Migration; METADATA_VERSION as usize] = [v0_to_v1];
+
+fn v0_to_v1(table: &mut Table) {
+    // Early files carried the recipient key fingerprint, which is now read
+    // from the encrypted secret itself.
+    table.remove("fingerprint");
+}
+
+pub fn version_of(table: &Table) -> u32 {
+    table
+        .get(VERSION_FIELD)
+        .and_then(TomlValue::as_integer)
+        .and_then(|v| u32::try_from(v).ok())
+        .unwrap_or(0)
+}
+
+pub fn stored_version(text: &str) -> u32 {
+    text.parse::<Table>()
+        .map(|table| version_of(&table))
+        .unwrap_or(0)
+}
+
+pub fn migrate(table: &mut Table) -> Result<u32, String> {
+    let from = version_of(table);
+
+    if from > METADATA_VERSION {
+        return Err(format!(
+            "Metadata version {from} is newer than supported version {METADATA_VERSION}"
+        ));
+    }
+
+    for migration in &MIGRATIONS[from as usize..] {
+        migration(table);
+    }
+
+    table.insert(
+        VERSION_FIELD.to_string(),
+        TomlValue::Integer(i64::from(METADATA_VERSION)),
+    );
+
+    Ok(from)
+}
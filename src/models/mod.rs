@@ -1,5 +1,19 @@
+pub mod audit;
+pub mod breach;
 pub mod config;
+pub mod content;
+pub mod filter;
+pub mod generator;
+pub mod index;
 pub mod key_manager;
 pub mod metadata;
+pub mod migration;
+pub mod otp;
+pub mod query;
+pub mod saved_query;
+pub mod schema;
+pub mod search;
 pub mod secret;
 pub mod secret_manager;
+pub mod unlock;
+pub mod watcher;
@@ -0,0 +1,419 @@
+use serde_json::{Number, Value};
+
+use crate::{
+    error::Error,
+    models::filter::{
+        FieldCondition, Filter, GlobPattern, Operator, RegexPattern,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Quoted(String),
+    Op(&'static str),
+    LParen,
+    RParen,
+}
+
+const OPERATORS: [&str; 8] = [">=", "<=", "!=", ">", "<", "=", ":", "~"];
+
+fn tokenize(input: &str) -> Result<Vec<(usize, Token)>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(position, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '(' {
+            chars.next();
+            tokens.push((position, Token::LParen));
+        } else if c == ')' {
+            chars.next();
+            tokens.push((position, Token::RParen));
+        } else if c == '"' {
+            chars.next();
+
+            let mut value = String::new();
+            let mut closed = false;
+
+            while let Some((_, c)) = chars.next() {
+                match c {
+                    '"' => {
+                        closed = true;
+                        break;
+                    }
+                    '\\' => match chars.next() {
+                        Some((_, escaped)) => value.push(escaped),
+                        None => break,
+                    },
+                    _ => value.push(c),
+                }
+            }
+
+            if !closed {
+                return Err(Error::InvalidQuery {
+                    position,
+                    message: "Unterminated string".to_string(),
+                });
+            }
+
+            tokens.push((position, Token::Quoted(value)));
+        } else if let Some(op) = OPERATORS
+            .iter()
+            .find(|op| input[position..].starts_with(**op))
+        {
+            for _ in 0..op.len() {
+                chars.next();
+            }
+
+            tokens.push((position, Token::Op(op)));
+        } else {
+            let mut word = String::new();
+            // A value may contain `:` so that timestamps such as
+            // `expires_at<2026-01-01T00:00:00Z` need no quoting.
+            let is_value = matches!(tokens.last(), Some((_, Token::Op(_))));
+
+            while let Some(&(offset, c)) = chars.peek() {
+                if c.is_whitespace()
+                    || c == '('
+                    || c == ')'
+                    || c == '"'
+                    || OPERATORS.iter().any(|op| {
+                        input[offset..].starts_with(op)
+                            && !(is_value && *op == ":")
+                    })
+                {
+                    break;
+                }
+
+                word.push(c);
+                chars.next();
+            }
+
+            tokens.push((position, Token::Word(word)));
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |(offset, _)| *offset)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).map(|(_, t)| t.clone());
+
+        self.position += 1;
+        token
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error::InvalidQuery {
+            position: self.offset(),
+            message: message.into(),
+        }
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w == keyword)
+    }
+
+    fn parse_or(&mut self) -> Result<Filter, Error> {
+        let mut filters = vec![self.parse_and()?];
+
+        while self.is_keyword("OR") {
+            self.next();
+            filters.push(self.parse_and()?);
+        }
+
+        Ok(match filters.len() {
+            1 => filters.remove(0),
+            _ => Filter::Or(filters),
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Filter, Error> {
+        let mut filters = vec![self.parse_unary()?];
+
+        loop {
+            if self.is_keyword("AND") {
+                self.next();
+            } else if self.peek().is_none()
+                || self.is_keyword("OR")
+                || self.peek() == Some(&Token::RParen)
+            {
+                break;
+            }
+
+            filters.push(self.parse_unary()?);
+        }
+
+        Ok(match filters.len() {
+            1 => filters.remove(0),
+            _ => Filter::And(filters),
+        })
+    }
+
+    fn parse_unary(&mut self) -> Result<Filter, Error> {
+        if self.is_keyword("NOT") {
+            self.next();
+
+            return Ok(Filter::Not(Box::new(self.parse_unary()?)));
+        }
+
+        match self.peek() {
+            Some(Token::LParen) => {
+                self.next();
+
+                let filter = self.parse_or()?;
+
+                match self.next() {
+                    Some(Token::RParen) => Ok(filter),
+                    _ => {
+                        self.position -= 1;
+                        Err(self.error("Expected ')'"))
+                    }
+                }
+            }
+            Some(Token::Word(_)) => self.parse_condition(),
+            Some(_) => Err(self.error("Expected a field name")),
+            None => Err(self.error("Unexpected end of query")),
+        }
+    }
+
+    fn parse_condition(&mut self) -> Result<Filter, Error> {
+        let field = match self.next() {
+            Some(Token::Word(field)) => field,
+            _ => unreachable!("parse_condition is only called on a word"),
+        };
+
+        let op = match self.peek() {
+            Some(Token::Op(op)) => *op,
+            _ => return Err(self.error("Expected an operator after field")),
+        };
+
+        self.next();
+
+        let position = self.offset();
+        let invalid =
+            |message: String| Error::InvalidQuery { position, message };
+        let (value, quoted) = match self.next() {
+            Some(Token::Word(value)) => (value, false),
+            Some(Token::Quoted(value)) => (value, true),
+            _ => {
+                self.position -= 1;
+                return Err(self.error("Expected a value after operator"));
+            }
+        };
+
+        let operator = match op {
+            ":" if !quoted && value == "*" => Operator::Exists(true),
+            ":" if !quoted && value.contains(['*', '?', '[']) => {
+                Operator::Glob(
+                    GlobPattern::new(&value)
+                        .map_err(|e| invalid(format!("Invalid glob: {e}")))?,
+                )
+            }
+            ":" => Operator::In(vec![literal(value, quoted)]),
+            "=" => Operator::Eq(literal(value, quoted)),
+            "!=" => Operator::Ne(literal(value, quoted)),
+            ">" => Operator::Gt(literal(value, quoted)),
+            ">=" => Operator::Gte(literal(value, quoted)),
+            "<" => Operator::Lt(literal(value, quoted)),
+            "<=" => Operator::Lte(literal(value, quoted)),
+            "~" => Operator::Regex(
+                RegexPattern::new(&value)
+                    .map_err(|e| invalid(format!("Invalid regex: {e}")))?,
+            ),
+            _ => unreachable!("unknown operator {op}"),
+        };
+
+        Ok(Filter::Condition(FieldCondition { field, operator }))
+    }
+}
+
+fn literal(value: String, quoted: bool) -> Value {
+    if quoted {
+        return Value::String(value);
+    }
+
+    match value.as_str() {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => value
+            .parse::<i64>()
+            .map(Value::from)
+            .ok()
+            .or_else(|| {
+                value
+                    .parse::<f64>()
+                    .ok()
+                    .and_then(Number::from_f64)
+                    .map(Value::Number)
+            })
+            .unwrap_or(Value::String(value)),
+    }
+}
+
+pub fn parse_query(input: &str) -> Result<Filter, Error> {
+    let mut parser = Parser {
+        tokens: tokenize(input)?,
+        position: 0,
+        end: input.len(),
+    };
+    let filter = parser.parse_or()?;
+
+    if parser.peek().is_some() {
+        return Err(parser.error("Unexpected token"));
+    }
+
+    Ok(filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn condition(query: &str) -> FieldCondition {
+        match parse_query(query).unwrap() {
+            Filter::Condition(condition) => condition,
+            other => panic!("Expected a condition, got {other:?}"),
+        }
+    }
+
+    /// The shape of a parsed query, with conditions reduced to their field.
+    fn shape(filter: &Filter) -> String {
+        let join = |filters: &[Filter], separator: &str| {
+            let parts: Vec<String> = filters.iter().map(shape).collect();
+
+            format!("({})", parts.join(separator))
+        };
+
+        match filter {
+            Filter::Not(filter) => format!("!{}", shape(filter)),
+            Filter::And(filters) => join(filters, " & "),
+            Filter::Or(filters) => join(filters, " | "),
+            Filter::Condition(condition) => condition.field.clone(),
+        }
+    }
+
+    fn error(query: &str) -> (usize, String) {
+        match parse_query(query) {
+            Err(Error::InvalidQuery { position, message }) => {
+                (position, message)
+            }
+            other => panic!("Expected a query error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let parsed = |query: &str| shape(&parse_query(query).unwrap());
+
+        assert_eq!(parsed("a:1 OR b:2 AND c:3"), "(a | (b & c))");
+        assert_eq!(parsed("a:1 AND b:2 OR c:3"), "((a & b) | c)");
+        assert_eq!(parsed("a:1 b:2 OR NOT c:3"), "((a & b) | !c)");
+    }
+
+    #[test]
+    fn parentheses_group_before_precedence() {
+        let parsed = |query: &str| shape(&parse_query(query).unwrap());
+
+        assert_eq!(parsed("(a:1 OR b:2) AND c:3"), "((a | b) & c)");
+        assert_eq!(parsed("NOT (a:1 OR b:2)"), "!(a | b)");
+        assert_eq!(parsed("((a:1))"), "a");
+    }
+
+    #[test]
+    fn malformed_queries_report_where_they_fail() {
+        let expected = [
+            ("name:\"abc", 5, "Unterminated string"),
+            ("(a:1 OR b:2", 11, "Expected ')'"),
+            ("a:1)", 3, "Unexpected token"),
+            ("a:", 2, "Expected a value after operator"),
+            ("a b:1", 2, "Expected an operator after field"),
+            ("a:1 AND", 7, "Unexpected end of query"),
+            ("= x", 0, "Expected a field name"),
+        ];
+
+        for (query, position, message) in expected {
+            assert_eq!(
+                error(query),
+                (position, message.to_string()),
+                "for {query}"
+            );
+        }
+
+        let (position, message) = error("path~\"(\"");
+
+        assert_eq!(position, 5);
+        assert!(message.starts_with("Invalid regex"), "{message}");
+    }
+
+    #[test]
+    fn timestamps_after_a_comparison_need_no_quotes() {
+        let condition = condition("expires_at<2026-01-01T00:00:00Z");
+
+        assert_eq!(condition.field, "expires_at");
+        assert!(matches!(
+            condition.operator,
+            Operator::Lt(Value::String(ref value))
+                if value == "2026-01-01T00:00:00Z"
+        ));
+    }
+
+    #[test]
+    fn unquoted_timestamps_end_at_the_next_token() {
+        let condition = condition("updated_at>=2026-03-01T12:30:00+02:00");
+
+        assert!(matches!(
+            condition.operator,
+            Operator::Gte(Value::String(ref value))
+                if value == "2026-03-01T12:30:00+02:00"
+        ));
+
+        let filter =
+            parse_query("(expires_at<2026-01-01T00:00:00Z) category:work")
+                .unwrap();
+
+        assert_eq!(shape(&filter), "(expires_at & category)");
+
+        let Filter::And(filters) = filter else {
+            unreachable!();
+        };
+
+        assert!(matches!(
+            &filters[0],
+            Filter::Condition(FieldCondition {
+                operator: Operator::Lt(Value::String(value)),
+                ..
+            }) if value == "2026-01-01T00:00:00Z"
+        ));
+    }
+
+    #[test]
+    fn colon_still_separates_field_and_value() {
+        let condition = condition("category:work");
+
+        assert_eq!(condition.field, "category");
+        assert!(matches!(
+            condition.operator,
+            Operator::In(ref values) if values == &[Value::from("work")]
+        ));
+    }
+}
@@ -0,0 +1,183 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    error::{Error, IoContext, Result},
+    models::config::Config,
+    utils::fs::{secure_write, set_secure_dir_permissions},
+};
+
+const QUERIES_FILE_NAME: &str = "queries.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedQuery {
+    pub name: String,
+    pub description: Option<String>,
+    pub request: Value,
+    pub saved_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SavedQueries {
+    pub queries: BTreeMap<String, SavedQuery>,
+}
+
+impl SavedQueries {
+    /// Saved queries live at the root of `metadata_dir` so that they are
+    /// shared along with the rest of the vault.
+    pub fn queries_path(config: &Config) -> PathBuf {
+        config.metadata_dir.join(QUERIES_FILE_NAME)
+    }
+
+    fn legacy_queries_path(config: &Config) -> PathBuf {
+        config.base_dir.join(QUERIES_FILE_NAME)
+    }
+
+    /// Whether `path` is the saved queries file or its temporary copy,
+    /// which are the only files in `metadata_dir` that are not metadata.
+    pub fn is_queries_file(config: &Config, path: &Path) -> bool {
+        path.parent() == Some(config.metadata_dir.as_path())
+            && path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with(QUERIES_FILE_NAME))
+    }
+
+    pub fn load(config: &Config) -> Result<Self> {
+        let queries_path = [
+            Self::queries_path(config),
+            Self::legacy_queries_path(config),
+        ]
+        .into_iter()
+        .find(|path| path.exists());
+
+        let Some(queries_path) = queries_path else {
+            return Ok(Self::default());
+        };
+
+        let text = fs::read_to_string(&queries_path).io_context(format!(
+            "Failed to read saved queries {}",
+            queries_path.display()
+        ))?;
+
+        serde_json::from_str(&text).map_err(|e| {
+            Error::Internal(format!("Failed to parse saved queries: {e}"))
+        })
+    }
+
+    pub fn save(&self, config: &Config) -> Result<()> {
+        let queries_path = Self::queries_path(config);
+        let temp_path = queries_path.with_extension("json.tmp");
+        let text = serde_json::to_string_pretty(self).map_err(|e| {
+            Error::Internal(format!("Failed to serialize saved queries: {e}"))
+        })?;
+
+        if !config.metadata_dir.exists() {
+            fs::create_dir_all(&config.metadata_dir)
+                .and_then(|_| set_secure_dir_permissions(&config.metadata_dir))
+                .io_context("Failed to create metadata directory")?;
+        }
+
+        secure_write(&temp_path, text)
+            .io_context("Failed to write saved queries")?;
+        fs::rename(&temp_path, &queries_path)
+            .io_context("Failed to replace saved queries")?;
+
+        let legacy_path = Self::legacy_queries_path(config);
+
+        if legacy_path.exists() {
+            fs::remove_file(&legacy_path)
+                .io_context("Failed to remove legacy saved queries")?;
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Result<&SavedQuery> {
+        self.queries
+            .get(name)
+            .ok_or_else(|| Error::QueryNotFound(name.to_string()))
+    }
+
+    pub fn insert(
+        &mut self,
+        name: &str,
+        description: Option<String>,
+        request: Value,
+    ) -> Result<()> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-_.".contains(c))
+        {
+            return Err(Error::InvalidParams(format!(
+                "Invalid saved query name '{name}', use letters, digits, '-', '_' or '.'"
+            )));
+        }
+
+        self.queries.insert(
+            name.to_string(),
+            SavedQuery {
+                name: name.to_string(),
+                description,
+                request,
+                saved_at: Utc::now(),
+            },
+        );
+
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<SavedQuery> {
+        self.queries
+            .remove(name)
+            .ok_or_else(|| Error::QueryNotFound(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_move_from_base_dir_into_the_vault() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            base_dir: dir.path().to_path_buf(),
+            secrets_dir: dir.path().join("secrets"),
+            metadata_dir: dir.path().join(".metadata"),
+            keys_dir: dir.path().join(".keys"),
+            ..Config::default()
+        };
+        let mut legacy = SavedQueries::default();
+
+        legacy.insert("stale", None, Value::Null).unwrap();
+        fs::write(
+            dir.path().join(QUERIES_FILE_NAME),
+            serde_json::to_string(&legacy).unwrap(),
+        )
+        .unwrap();
+
+        let mut queries = SavedQueries::load(&config).unwrap();
+
+        assert!(queries.get("stale").is_ok());
+        assert!(matches!(
+            queries.get("missing"),
+            Err(Error::QueryNotFound(name)) if name == "missing"
+        ));
+
+        queries.insert("prod", None, Value::Null).unwrap();
+        queries.save(&config).unwrap();
+
+        assert!(config.metadata_dir.join(QUERIES_FILE_NAME).exists());
+        assert!(!dir.path().join(QUERIES_FILE_NAME).exists());
+        assert_eq!(SavedQueries::load(&config).unwrap().queries.len(), 2);
+    }
+}
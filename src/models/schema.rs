@@ -0,0 +1,413 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+};
+
+use regex::Regex;
+use serde::{Deserialize, Serialize, Serializer};
+use toml::Value as TomlValue;
+
+use crate::{
+    error::{Error, Result},
+    models::metadata::BaseMetadata,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldKind {
+    String,
+    Integer,
+    Float,
+    Boolean,
+    Datetime,
+    Array,
+    Table,
+}
+
+// Patterns are compiled and bounds checked against the kind while the config
+// is loaded, so a broken schema fails at startup rather than on every secret.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "RawFieldSchema")]
+pub struct FieldSchema {
+    pub required: bool,
+    pub kind: Option<FieldKind>,
+    #[serde(rename = "enum")]
+    pub allowed: Option<Vec<TomlValue>>,
+    #[serde(serialize_with = "serialize_pattern")]
+    pub pattern: Option<Regex>,
+    pub min: Option<f64>,
+    pub max: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct RawFieldSchema {
+    #[serde(default)]
+    required: bool,
+    kind: Option<FieldKind>,
+    #[serde(rename = "enum")]
+    allowed: Option<Vec<TomlValue>>,
+    pattern: Option<String>,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+pub type MetadataSchema = BTreeMap<String, FieldSchema>;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchemaConfig {
+    #[serde(default)]
+    pub r#type: HashMap<String, MetadataSchema>,
+    #[serde(default)]
+    pub category: HashMap<String, MetadataSchema>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FieldViolation {
+    pub field: String,
+    pub reason: String,
+}
+
+impl FieldKind {
+    fn of(value: &TomlValue) -> Self {
+        match value {
+            TomlValue::String(_) => FieldKind::String,
+            TomlValue::Integer(_) => FieldKind::Integer,
+            TomlValue::Float(_) => FieldKind::Float,
+            TomlValue::Boolean(_) => FieldKind::Boolean,
+            TomlValue::Datetime(_) => FieldKind::Datetime,
+            TomlValue::Array(_) => FieldKind::Array,
+            TomlValue::Table(_) => FieldKind::Table,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            FieldKind::String => "string",
+            FieldKind::Integer => "integer",
+            FieldKind::Float => "float",
+            FieldKind::Boolean => "boolean",
+            FieldKind::Datetime => "datetime",
+            FieldKind::Array => "array",
+            FieldKind::Table => "table",
+        }
+    }
+}
+
+fn serialize_pattern<S: Serializer>(
+    pattern: &Option<Regex>,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    pattern.as_ref().map(Regex::as_str).serialize(serializer)
+}
+
+impl TryFrom<RawFieldSchema> for FieldSchema {
+    type Error = String;
+
+    fn try_from(raw: RawFieldSchema) -> std::result::Result<Self, String> {
+        if (raw.min.is_some() || raw.max.is_some())
+            && let Some(kind) = raw.kind
+            && !matches!(kind, FieldKind::Integer | FieldKind::Float)
+        {
+            return Err(format!(
+                "min and max only apply to numbers, not to kind {}",
+                kind.name()
+            ));
+        }
+
+        if raw.pattern.is_some()
+            && let Some(kind) = raw.kind
+            && kind != FieldKind::String
+        {
+            return Err(format!(
+                "pattern only applies to strings, not to kind {}",
+                kind.name()
+            ));
+        }
+
+        let pattern = raw
+            .pattern
+            .map(|pattern| {
+                Regex::new(&pattern)
+                    .map_err(|e| format!("invalid pattern '{pattern}': {e}"))
+            })
+            .transpose()?;
+
+        Ok(Self {
+            required: raw.required,
+            kind: raw.kind,
+            allowed: raw.allowed,
+            pattern,
+            min: raw.min,
+            max: raw.max,
+        })
+    }
+}
+
+fn get_nested<'a>(value: &'a TomlValue, field: &str) -> Option<&'a TomlValue> {
+    field
+        .split('.')
+        .try_fold(value, |current, key| current.as_table()?.get(key))
+}
+
+impl FieldSchema {
+    fn check(&self, value: Option<&TomlValue>) -> Option<String> {
+        let Some(value) = value else {
+            return self.required.then(|| "is required".to_string());
+        };
+
+        if let Some(kind) = self.kind
+            && FieldKind::of(value) != kind
+            && !(kind == FieldKind::Float
+                && FieldKind::of(value) == FieldKind::Integer)
+        {
+            return Some(format!("must be of type {}", kind.name()));
+        }
+
+        if let Some(allowed) = &self.allowed
+            && !allowed.contains(value)
+        {
+            let allowed: Vec<String> =
+                allowed.iter().map(TomlValue::to_string).collect();
+
+            return Some(format!("must be one of {}", allowed.join(", ")));
+        }
+
+        if let Some(pattern) = &self.pattern
+            && !value.as_str().is_some_and(|s| pattern.is_match(s))
+        {
+            return Some(format!("must match pattern '{}'", pattern.as_str()));
+        }
+
+        let number = match value {
+            TomlValue::Integer(i) => Some(*i as f64),
+            TomlValue::Float(f) => Some(*f),
+            _ => None,
+        };
+
+        if (self.min.is_some() || self.max.is_some()) && number.is_none() {
+            return Some("must be a number".to_string());
+        }
+
+        if let (Some(min), Some(number)) = (self.min, number)
+            && number < min
+        {
+            return Some(format!("must be at least {min}"));
+        }
+
+        if let (Some(max), Some(number)) = (self.max, number)
+            && number > max
+        {
+            return Some(format!("must be at most {max}"));
+        }
+
+        None
+    }
+}
+
+impl SchemaConfig {
+    pub fn violations(&self, metadata: &BaseMetadata) -> Vec<FieldViolation> {
+        let schemas = [
+            metadata.r#type.as_ref().and_then(|t| self.r#type.get(t)),
+            metadata
+                .category
+                .as_ref()
+                .and_then(|c| self.category.get(c)),
+        ];
+
+        if schemas.iter().all(Option::is_none) {
+            return Vec::new();
+        }
+
+        let Ok(value) = TomlValue::try_from(metadata) else {
+            return vec![FieldViolation {
+                field: String::new(),
+                reason: "could not be converted for validation".to_string(),
+            }];
+        };
+
+        schemas
+            .into_iter()
+            .flatten()
+            .flat_map(|schema| schema.iter())
+            .filter_map(|(field, field_schema)| {
+                field_schema.check(get_nested(&value, field)).map(|reason| {
+                    FieldViolation {
+                        field: field.clone(),
+                        reason,
+                    }
+                })
+            })
+            .collect()
+    }
+
+    pub fn validate(
+        &self,
+        relative_path: &Path,
+        metadata: &BaseMetadata,
+    ) -> Result<()> {
+        let violations = self.violations(metadata);
+
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        Err(Error::SchemaViolation {
+            path: relative_path.to_path_buf(),
+            violations,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn schema(toml: &str) -> SchemaConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn login(extra: &[(&str, TomlValue)]) -> BaseMetadata {
+        BaseMetadata {
+            r#type: Some("login".to_string()),
+            extra: Some(
+                extra
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.clone()))
+                    .collect::<HashMap<_, _>>(),
+            ),
+            ..BaseMetadata::default()
+        }
+    }
+
+    fn reasons(config: &SchemaConfig, metadata: &BaseMetadata) -> Vec<String> {
+        config
+            .violations(metadata)
+            .into_iter()
+            .map(|violation| {
+                format!("{} {}", violation.field, violation.reason)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fields_are_checked_against_their_schema() {
+        let config = schema(
+            r#"
+            [type.login]
+            url = { required = true, kind = "string", pattern = "^https://" }
+            env = { enum = ["prod", "dev"] }
+            port = { kind = "integer", min = 1, max = 65535 }
+            weight = { kind = "float", min = 0.5 }
+            "#,
+        );
+        let valid = login(&[
+            ("url", TomlValue::from("https://example.com")),
+            ("env", TomlValue::from("prod")),
+            ("port", TomlValue::from(443)),
+            ("weight", TomlValue::from(1)),
+        ]);
+
+        assert!(reasons(&config, &valid).is_empty());
+        assert_eq!(reasons(&config, &login(&[])), ["url is required"]);
+        assert_eq!(
+            reasons(
+                &config,
+                &login(&[
+                    ("url", TomlValue::from("http://example.com")),
+                    ("env", TomlValue::from("staging")),
+                    ("port", TomlValue::from("443")),
+                    ("weight", TomlValue::from(0.25)),
+                ])
+            ),
+            [
+                "env must be one of \"prod\", \"dev\"",
+                "port must be of type integer",
+                "url must match pattern '^https://'",
+                "weight must be at least 0.5",
+            ]
+        );
+        assert_eq!(
+            reasons(
+                &config,
+                &login(&[
+                    ("url", TomlValue::from("https://example.com")),
+                    ("port", TomlValue::from(70000)),
+                ])
+            ),
+            ["port must be at most 65535"]
+        );
+    }
+
+    #[test]
+    fn dotted_fields_reach_into_tables() {
+        let config = schema(
+            r#"
+            [category.work]
+            "login.user" = { required = true, kind = "string" }
+            "#,
+        );
+        let mut table = toml::Table::new();
+
+        table.insert("user".to_string(), TomlValue::from(7));
+
+        let metadata = BaseMetadata {
+            category: Some("work".to_string()),
+            extra: Some(HashMap::from([(
+                "login".to_string(),
+                TomlValue::Table(table),
+            )])),
+            ..BaseMetadata::default()
+        };
+
+        assert_eq!(
+            reasons(&config, &metadata),
+            ["login.user must be of type string"]
+        );
+        assert_eq!(
+            reasons(
+                &config,
+                &BaseMetadata {
+                    category: Some("work".to_string()),
+                    ..BaseMetadata::default()
+                }
+            ),
+            ["login.user is required"]
+        );
+    }
+
+    #[test]
+    fn bounds_without_a_kind_require_a_number() {
+        let config = schema("[type.login]\nsize = { max = 10 }");
+
+        assert_eq!(
+            reasons(&config, &login(&[("size", TomlValue::from("big"))])),
+            ["size must be a number"]
+        );
+    }
+
+    #[test]
+    fn broken_schemas_fail_to_load() {
+        for (toml, error) in [
+            (
+                "[type.login]\nurl = { pattern = \"(\" }",
+                "invalid pattern '('",
+            ),
+            (
+                "[type.login]\nname = { kind = \"string\", min = 1 }",
+                "min and max only apply to numbers, not to kind string",
+            ),
+            (
+                "[type.login]\nport = { kind = \"integer\", pattern = \"1\" }",
+                "pattern only applies to strings, not to kind integer",
+            ),
+        ] {
+            let message = toml::from_str::<SchemaConfig>(toml)
+                .unwrap_err()
+                .to_string();
+
+            assert!(message.contains(error), "{message}");
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use toml::Value as TomlValue;
+
+use crate::models::metadata::Metadata;
+
+const PATH_WEIGHT: u32 = 4;
+const NAME_WEIGHT: u32 = 3;
+const TAG_WEIGHT: u32 = 2;
+const TEXT_WEIGHT: u32 = 1;
+
+const EXACT_SCORE: f64 = 1.0;
+const PREFIX_SCORE: f64 = 0.6;
+const FUZZY_SCORE: f64 = 0.4;
+
+pub type Terms = BTreeMap<String, u32>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub path: PathBuf,
+    pub score: f64,
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn add_terms(terms: &mut Terms, text: &str, weight: u32) {
+    for token in tokenize(text) {
+        *terms.entry(token).or_default() += weight;
+    }
+}
+
+fn add_toml_terms(terms: &mut Terms, value: &TomlValue) {
+    match value {
+        TomlValue::String(s) => add_terms(terms, s, TEXT_WEIGHT),
+        TomlValue::Array(items) => {
+            items.iter().for_each(|item| add_toml_terms(terms, item))
+        }
+        TomlValue::Table(table) => {
+            table.values().for_each(|item| add_toml_terms(terms, item))
+        }
+        other => add_terms(terms, &other.to_string(), TEXT_WEIGHT),
+    }
+}
+
+pub fn index_terms(relative_path: &Path, metadata: &Metadata) -> Terms {
+    let template = &metadata.template;
+    let mut terms = Terms::new();
+
+    add_terms(&mut terms, &relative_path.to_string_lossy(), PATH_WEIGHT);
+
+    for name in [&template.r#type, &template.category].into_iter().flatten() {
+        add_terms(&mut terms, name, NAME_WEIGHT);
+    }
+
+    for tag in template.tags.iter().flatten() {
+        add_terms(&mut terms, tag, TAG_WEIGHT);
+    }
+
+    if let Some(description) = &template.description {
+        add_terms(&mut terms, description, TEXT_WEIGHT);
+    }
+
+    for value in template.extra.iter().flat_map(|extra| extra.values()) {
+        add_toml_terms(&mut terms, value);
+    }
+
+    terms
+}
+
+pub(crate) fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    char_edit_distance(&a, &b, max)
+}
+
+pub(crate) fn char_edit_distance(
+    a: &[char],
+    b: &[char],
+    max: usize,
+) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+
+        for (j, cb) in b.iter().enumerate() {
+            current[j + 1] = (previous[j] + usize::from(ca != cb))
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+        }
+
+        if current.iter().min().is_some_and(|&d| d > max) {
+            return None;
+        }
+
+        previous = current;
+    }
+
+    previous.last().copied().filter(|&d| d <= max)
+}
+
+fn max_edits(token: &str) -> usize {
+    match token.chars().count() {
+        0..=3 => 0,
+        4..=7 => 1,
+        _ => 2,
+    }
+}
+
+fn token_score(token: &str, terms: &Terms, fuzzy: bool) -> f64 {
+    terms
+        .iter()
+        .filter_map(|(term, &weight)| {
+            let quality = if term == token {
+                EXACT_SCORE
+            } else if term.starts_with(token) {
+                PREFIX_SCORE
+            } else if fuzzy
+                && edit_distance(token, term, max_edits(token)).is_some()
+            {
+                FUZZY_SCORE
+            } else {
+                return None;
+            };
+
+            Some(quality * f64::from(weight))
+        })
+        .fold(0.0, f64::max)
+}
+
+pub fn score(query: &[String], terms: &Terms, fuzzy: bool) -> Option<f64> {
+    let mut total = 0.0;
+
+    for token in query {
+        let score = token_score(token, terms, fuzzy);
+
+        if score == 0.0 {
+            return None;
+        }
+
+        total += score;
+    }
+
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::models::metadata::BaseMetadata;
+
+    fn terms(path: &str, template: BaseMetadata) -> Terms {
+        let metadata = Metadata {
+            path: PathBuf::from(path),
+            template,
+            ..Metadata::default()
+        };
+
+        index_terms(Path::new(path), &metadata)
+    }
+
+    fn search(text: &str, terms: &Terms, fuzzy: bool) -> Option<f64> {
+        score(&tokenize(text), terms, fuzzy)
+    }
+
+    #[test]
+    fn fields_are_weighted_by_where_the_word_appears() {
+        let in_path = terms("mail/github", BaseMetadata::default());
+        let in_tags = terms(
+            "web/code",
+            BaseMetadata {
+                tags: Some(vec!["GitHub".to_string()]),
+                ..BaseMetadata::default()
+            },
+        );
+        let in_text = terms(
+            "web/mirror",
+            BaseMetadata {
+                description: Some("Mirror of our GitHub repos".to_string()),
+                ..BaseMetadata::default()
+            },
+        );
+
+        assert_eq!(search("github", &in_path, false), Some(4.0));
+        assert_eq!(search("GITHUB", &in_tags, false), Some(2.0));
+        assert_eq!(search("github", &in_text, false), Some(1.0));
+    }
+
+    #[test]
+    fn exact_words_outrank_prefixes_and_typos() {
+        let terms = terms("web/github", BaseMetadata::default());
+        let exact = search("github", &terms, true).unwrap();
+        let prefix = search("git", &terms, true).unwrap();
+        let typo = search("githb", &terms, true).unwrap();
+
+        assert!(exact > prefix && prefix > typo, "{exact} {prefix} {typo}");
+        assert_eq!(search("githb", &terms, false), None);
+
+        // Short words have to match exactly or as a prefix.
+        assert_eq!(search("gtu", &terms, true), None);
+    }
+
+    #[test]
+    fn every_word_has_to_match_somewhere() {
+        let terms = terms(
+            "web/github",
+            BaseMetadata {
+                category: Some("work".to_string()),
+                extra: Some(HashMap::from([(
+                    "account".to_string(),
+                    TomlValue::Table(toml::toml! { login = "Octocat" }),
+                )])),
+                ..BaseMetadata::default()
+            },
+        );
+
+        assert_eq!(search("github work", &terms, false), Some(7.0));
+        assert_eq!(search("octocat", &terms, false), Some(1.0));
+        assert_eq!(search("github home", &terms, false), None);
+    }
+}
@@ -1,11 +1,10 @@
 use std::{
     fs,
-    io::{self, Write},
-    path::PathBuf,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
     sync::Arc,
 };
 
-use anyhow::{Context, Result};
 use chrono::Utc;
 use log;
 use sequoia_openpgp::{
@@ -24,20 +23,29 @@ use sequoia_openpgp::{
         Armorer, Encryptor, LiteralWriter, Message as StreamMessage, Recipient,
     },
 };
-use toml;
+use toml::{Table, Value as TomlValue};
+use zeroize::Zeroizing;
 
 use crate::{
+    error::{CryptoContext, Error, IoContext, Result},
     models::{
         config::Config,
+        content::SecretContent,
+        index::SharedIndex,
         key_manager::KeyManager,
-        metadata::{BaseMetadata, Metadata},
+        metadata::{BaseMetadata, Metadata, MetadataOp},
+        migration,
+        unlock::MetadataUnlocker,
     },
     utils::{
         checksum::compute_checksum,
         fs::{secure_create_dir_all, secure_write},
+        memory::{SecretBuffer, SecretString},
     },
 };
 
+const DECRYPT_CHUNK_SIZE: usize = 4096;
+
 struct DecryptHelper {
     keypair: KeyPair,
 }
@@ -87,93 +95,40 @@ impl DecryptionHelper for DecryptHelper {
 pub struct Secret {
     pub relative_path: PathBuf,
     pub config: Arc<Config>,
+    pub index: Arc<SharedIndex>,
 }
 
 impl Secret {
-    pub fn new(relative_path: PathBuf, config: Arc<Config>) -> Self {
+    pub fn new(
+        relative_path: PathBuf,
+        config: Arc<Config>,
+        index: Arc<SharedIndex>,
+    ) -> Self {
         Self {
             relative_path,
             config,
+            index,
         }
     }
 
-    fn recipient_certs(&self, key_manager: &KeyManager) -> Result<Vec<Cert>> {
+    pub(crate) fn recipient_certs(
+        &self,
+        key_manager: &KeyManager,
+    ) -> Result<Vec<Cert>> {
         if !self.secret_path()?.exists() {
-            return Err(anyhow::anyhow!("Secret file does not exist"));
+            return Err(Error::NotFound(self.relative_path.clone()));
         }
 
-        let content = self.content()?;
-        let message: Message = content
-            .parse()
-            .or_else(|_| Message::from_bytes(content.as_bytes()))
-            .context("Failed to parse secret as message")?;
-        let mut recipient_certs = Vec::new();
-
-        for pkt in message.packets().descendants() {
-            if let Packet::PKESK(pkesk) = pkt {
-                let keyid: KeyID = pkesk.recipient().into();
-
-                if let Some(cert) = key_manager.find_cert_by_keyid(&keyid) {
-                    recipient_certs.push(cert);
-                }
-            }
-        }
+        let recipient_certs = message_recipients(&self.content()?)?
+            .iter()
+            .filter_map(|keyid| key_manager.find_cert_by_keyid(keyid))
+            .collect();
 
         Ok(recipient_certs)
     }
 
-    fn unlock_keypair(
-        &self,
-        cert: &Cert,
-        password: &str,
-    ) -> Result<Option<KeyPair>> {
-        let policy = &StandardPolicy::new();
-
-        if let Some(key) = cert
-            .keys()
-            .secret()
-            .with_policy(policy, None)
-            .alive()
-            .revoked(false)
-            .for_storage_encryption()
-            .next()
-        {
-            let kp = key
-                .key()
-                .clone()
-                .parts_into_secret()
-                .context("Failed to get secret key parts")?
-                .decrypt_secret(&Password::from(password.to_string()));
-
-            if let Ok(decrypted_secret) = kp {
-                return Ok(Some(
-                    decrypted_secret
-                        .into_keypair()
-                        .context("Failed to build keypair")?,
-                ));
-            }
-        }
-
-        Ok(None)
-    }
-
-    fn decrypt_with_keypair(&self, keypair: &KeyPair) -> Result<String> {
-        let policy = &StandardPolicy::new();
-        let helper = DecryptHelper::new(keypair.clone());
-        let ciphertext = self.content()?;
-        let mut decryptor = DecryptorBuilder::from_bytes(ciphertext.as_bytes())
-            .context("Failed to create decryptor from ciphertext")?
-            .with_policy(policy, None, helper)
-            .context("Failed to configure decryptor with policy")?;
-        let mut plaintext_buf: Vec<u8> = Vec::new();
-
-        io::copy(&mut decryptor, &mut plaintext_buf)
-            .context("Failed to decrypt content with keypair")?;
-
-        let plaintext = String::from_utf8(plaintext_buf)
-            .context("Decrypted content is not valid UTF-8")?;
-
-        Ok(plaintext)
+    fn decrypt_with_keypair(&self, keypair: &KeyPair) -> Result<SecretString> {
+        decrypt_message(self.content()?.as_bytes(), keypair)
     }
 
     fn encrypt_with_certs(
@@ -198,37 +153,35 @@ impl Secret {
         }
 
         if recipients.is_empty() {
-            return Err(anyhow::anyhow!("No suitable encryption key found"));
+            return Err(Error::NoRecipients(
+                "No suitable encryption key found".to_string(),
+            ));
         }
 
         let mut encrypted = Vec::new();
         let message = StreamMessage::new(&mut encrypted);
         let message = Armorer::new(message)
             .build()
-            .context("Failed to armor message")?;
+            .crypto_context("Failed to armor message")?;
         let message = Encryptor::for_recipients(message, recipients)
             .build()
-            .context("Failed to build encryptor")?;
+            .crypto_context("Failed to build encryptor")?;
         let mut message = LiteralWriter::new(message)
             .build()
-            .context("Failed to build literal writer")?;
+            .crypto_context("Failed to build literal writer")?;
 
         message
             .write_all(content.as_bytes())
-            .context("Failed to write plaintext")?;
+            .io_context("Failed to write plaintext")?;
         message
             .finalize()
-            .context("Failed to finalize encryption")?;
+            .crypto_context("Failed to finalize encryption")?;
 
         Ok(encrypted)
     }
 
     pub fn metadata_path(&self) -> Result<PathBuf> {
-        Ok(self
-            .config
-            .metadata_dir
-            .join(&self.relative_path)
-            .with_extension("meta.toml"))
+        Ok(metadata_path(&self.config, &self.relative_path))
     }
 
     pub fn secret_path(&self) -> Result<PathBuf> {
@@ -240,36 +193,109 @@ impl Secret {
     }
 
     pub fn metadata(&self) -> Result<Metadata> {
+        self.read_metadata().map(|(metadata, _)| metadata)
+    }
+
+    pub(crate) fn read_metadata(&self) -> Result<(Metadata, String)> {
+        load_metadata(&self.config, &self.relative_path)
+    }
+
+    pub fn unlocked_metadata(&self, password: &str) -> Result<Metadata> {
+        MetadataUnlocker::new(
+            Arc::clone(&self.config),
+            SecretString::from(password),
+        )
+        .unlock(&self.metadata()?)
+    }
+
+    fn validate_metadata(&self, metadata: &BaseMetadata) -> Result<()> {
+        if let Some(rotate_every) = &metadata.rotate_every
+            && metadata.rotation_interval().is_none()
+        {
+            return Err(Error::InvalidMetadata {
+                path: self.relative_path.clone(),
+                reason: format!(
+                    "Invalid rotate_every duration '{rotate_every}'"
+                ),
+            });
+        }
+
+        self.config.schemas.validate(&self.relative_path, metadata)
+    }
+
+    fn seal_metadata(
+        &self,
+        metadata: Metadata,
+        fields: &[String],
+        certs: &[Cert],
+    ) -> Result<Metadata> {
+        let fields: Vec<String> = self
+            .config
+            .encrypted_fields
+            .iter()
+            .chain(fields)
+            .cloned()
+            .collect();
+
+        if fields.is_empty() {
+            return Ok(metadata);
+        }
+
+        let (mut plain, sealed) = metadata.seal(&fields)?;
+
+        if let Some(sealed) = sealed {
+            let text = toml::to_string(&sealed).map_err(|e| {
+                Error::Internal(format!(
+                    "Failed to serialize encrypted fields: {e}"
+                ))
+            })?;
+            let encrypted = self.encrypt_with_certs(&text, certs)?;
+
+            plain.encrypted = Some(
+                String::from_utf8(encrypted)
+                    .crypto_context("Encrypted data is not valid UTF-8")?,
+            );
+        }
+
+        Ok(plain)
+    }
+
+    pub(crate) fn write_metadata(&self, metadata: &mut Metadata) -> Result<()> {
         let metadata_path = self.metadata_path()?;
-        let text = fs::read_to_string(&metadata_path).with_context(|| {
-            format!("Failed to read metadata from {}", metadata_path.display())
-        })?;
 
-        Ok(toml::from_str(&text).context("Failed to parse metadata TOML")?)
+        metadata.checksum_meta = metadata.compute_checksum_meta()?;
+
+        secure_write(&metadata_path, serialize_metadata(metadata)?).io_context(
+            format!(
+                "Failed to write metadata file {}",
+                metadata_path.display()
+            ),
+        )
     }
 
     pub fn content(&self) -> Result<String> {
         let secret_path = self.secret_path()?;
 
-        fs::read_to_string(&secret_path).with_context(|| {
-            format!("Failed to read plaintext from {}", secret_path.display())
-        })
+        if !secret_path.exists() {
+            return Err(Error::NotFound(self.relative_path.clone()));
+        }
+
+        fs::read_to_string(&secret_path).io_context(format!(
+            "Failed to read plaintext from {}",
+            secret_path.display()
+        ))
     }
 
-    pub fn plaintext_content(&self, password: &str) -> Result<String> {
+    pub fn plaintext_content(&self, password: &str) -> Result<SecretString> {
         let key_manager = KeyManager {
             config: self.config.clone(),
         };
 
-        for cert in self.recipient_certs(&key_manager).unwrap_or(vec![]) {
-            let maybe_keypair = self.unlock_keypair(&cert, password)?;
+        for cert in self.recipient_certs(&key_manager)? {
+            let maybe_keypair = unlock_keypair(&cert, password)?;
             let keypair = match maybe_keypair {
                 Some(kp) => kp,
-                None => {
-                    return Err(anyhow::anyhow!(
-                        "Failed to unlock keypair with provided password"
-                    ));
-                }
+                None => return Err(Error::InvalidPassword),
             };
             let plaintext = self.decrypt_with_keypair(&keypair)?;
 
@@ -281,24 +307,57 @@ impl Secret {
             return Ok(plaintext);
         }
 
-        Err(anyhow::anyhow!("Failed to decrypt secret"))
+        Err(Error::NoRecipients(
+            "No known key can decrypt this secret".to_string(),
+        ))
+    }
+
+    pub fn plaintext_field(
+        &self,
+        field: &str,
+        password: &str,
+    ) -> Result<SecretString> {
+        let content = self.plaintext_content(password)?;
+
+        SecretContent::parse(&content, self.metadata()?.content_format())?
+            .get(field)
+    }
+
+    pub fn content_with_field(
+        &self,
+        field: &str,
+        value: &str,
+        password: &str,
+    ) -> Result<SecretString> {
+        let mut content = SecretContent::parse(
+            &self.plaintext_content(password)?,
+            self.metadata()?.content_format(),
+        )?;
+
+        content.set(field, value)?;
+        content.render()
     }
 
     pub fn create(
         &self,
         content: &str,
         metadata: &BaseMetadata,
+        encrypt_fields: &[String],
         fingerprints: &[&str],
     ) -> Result<&Self> {
         let secret_path = self.secret_path()?;
         let metadata_path = self.metadata_path()?;
 
         if secret_path.exists() || metadata_path.exists() {
-            return Err(anyhow::anyhow!(
-                "Secret or metadata file already exists"
-            ));
+            return Err(Error::AlreadyExists(self.relative_path.clone()));
         }
 
+        self.validate_metadata(metadata)?;
+        SecretContent::parse(
+            content,
+            metadata.content_format.unwrap_or_default(),
+        )?;
+
         let certs = fingerprints
             .iter()
             .map(|fp| {
@@ -308,72 +367,87 @@ impl Secret {
                 .get_public_cert(fp)
             })
             .collect::<Result<Vec<_>>>()?;
-        let encrypted = self.encrypt_with_certs(content, certs)?;
+        let encrypted = self.encrypt_with_certs(content, &certs)?;
         let encrypted_str = String::from_utf8(encrypted.clone())
-            .context("Encrypted data is not valid UTF-8")?;
+            .crypto_context("Encrypted data is not valid UTF-8")?;
         let checksum_main = compute_checksum(&encrypted_str);
-        let mut meta = Metadata {
-            path: self.relative_path.clone(),
-            checksum_main,
-            checksum_meta: String::new(),
-            ..metadata.clone().into()
-        };
-        let mut metadata_str = toml::to_string_pretty(&meta)
-            .context("Failed to serialize metadata")?;
+        let mut meta = self.seal_metadata(
+            Metadata {
+                path: self.relative_path.clone(),
+                checksum_main,
+                checksum_meta: String::new(),
+                ..metadata.clone().into()
+            },
+            encrypt_fields,
+            &certs,
+        )?;
+        let mut metadata_str = serialize_metadata(&meta)?;
 
         meta.checksum_meta = compute_checksum(&metadata_str);
-        metadata_str = toml::to_string_pretty(&meta)
-            .context("Failed to serialize metadata")?;
+        metadata_str = serialize_metadata(&meta)?;
 
         // observe
         if let Some(parent) = secret_path.parent() {
-            secure_create_dir_all(parent, &self.config.secrets_dir).context(
-                format!(
+            secure_create_dir_all(parent, &self.config.secrets_dir)
+                .io_context(format!(
                     "Failed to create secret directory {}",
                     parent.display(),
-                ),
-            )?;
+                ))?;
         }
         if let Some(parent) = metadata_path.parent() {
             secure_create_dir_all(parent, &self.config.metadata_dir)
-                .context("Failed to create metadata directory")?;
+                .io_context("Failed to create metadata directory")?;
         }
 
         secure_write(&secret_path, encrypted)
-            .context("Failed to write encrypted secret file")?;
+            .io_context("Failed to write encrypted secret file")?;
 
         secure_write(&metadata_path, metadata_str)
-            .context("Failed to write updated metadata file")?;
+            .io_context("Failed to write updated metadata file")?;
+
+        self.index.update(&self.config, &[&self.relative_path]);
 
         log::info!("Created secret: {}", self.relative_path.display());
 
         Ok(self)
     }
 
+    /// Replaces the content and/or metadata of the secret. `rotated_at` is
+    /// only bumped when `password_changed` is set, so editing another content
+    /// field does not count as a rotation.
+    #[allow(clippy::too_many_arguments)]
     pub fn update(
         &self,
         content: Option<&str>,
+        password_changed: bool,
         metadata: Option<&BaseMetadata>,
+        ops: &[MetadataOp],
+        encrypt_fields: &[String],
         fingerprints: Option<&[&str]>,
         password: &str,
     ) -> Result<&Self> {
-        if content.is_none() && metadata.is_none() && fingerprints.is_none() {
-            return Err(anyhow::anyhow!("No Changes were mode"));
+        if content.is_none()
+            && metadata.is_none()
+            && ops.is_empty()
+            && encrypt_fields.is_empty()
+            && fingerprints.is_none()
+        {
+            return Err(Error::InvalidParams(
+                "No changes were requested".to_string(),
+            ));
         }
 
         let secret_path = self.secret_path()?;
         let metadata_path = self.metadata_path()?;
 
         if !secret_path.exists() || !metadata_path.exists() {
-            return Err(anyhow::anyhow!(
-                "Secret or metadata file does not exist"
-            ));
+            return Err(Error::NotFound(self.relative_path.clone()));
         }
 
         if let Some(kfs) = fingerprints {
             if kfs.is_empty() {
-                return Err(anyhow::anyhow!(
-                    "Provided recipients must not be empty"
+                return Err(Error::InvalidParams(
+                    "Provided recipients must not be empty".to_string(),
                 ));
             }
         }
@@ -384,15 +458,15 @@ impl Secret {
         let exsting_certificates = self.recipient_certs(&key_manager)?;
 
         if exsting_certificates.is_empty() {
-            return Err(anyhow::anyhow!(
-                "No recipients found in encrypted secret"
+            return Err(Error::NoRecipients(
+                "No recipients found in encrypted secret".to_string(),
             ));
         }
 
         let mut unlocked_key_pair: Option<KeyPair> = None;
 
-        for exsting_certificate in exsting_certificates {
-            let key_pair = self.unlock_keypair(&exsting_certificate, password);
+        for exsting_certificate in &exsting_certificates {
+            let key_pair = unlock_keypair(exsting_certificate, password);
 
             if key_pair.is_ok() {
                 unlocked_key_pair = key_pair.unwrap_or(None);
@@ -401,72 +475,122 @@ impl Secret {
         }
 
         if unlocked_key_pair.is_none() {
-            return Err(anyhow::anyhow!(
-                "Password could not unlock any recipient key"
-            ));
+            return Err(Error::InvalidPassword);
         }
 
-        let mut updated_metadata = self
-            .metadata()
-            .context("Failed to read existing metadata for merging")?;
-        let mut metadata_str = String::new();
+        let unlocker = MetadataUnlocker::new(
+            Arc::clone(&self.config),
+            SecretString::from(password),
+        );
+        let (mut updated_metadata, mut sealed_fields) =
+            unlocker.unseal(&self.metadata()?)?;
+        let previous_format = updated_metadata.content_format();
 
         if let Some(base) = metadata {
-            updated_metadata = updated_metadata.merge(
-                &base.clone().into()
-            ).context(
-                "Failed to merge provided BaseMetadata into existing metadata",
-            )?;
+            updated_metadata = updated_metadata.merge(base)?;
         }
 
-        updated_metadata.checksum_meta = String::new();
-        updated_metadata.updated_at = Utc::now();
+        if !ops.is_empty() {
+            updated_metadata = updated_metadata.apply(ops)?;
+        }
 
-        if content.is_some() || fingerprints.is_some() {
-            let mut updated_recipient_certs = exsting_certificates;
-            let mut updated_content = content.unwrap_or_default().to_string();
-
-            if fingerprints.is_some() {
-                updated_recipient_certs = fingerprints
-                    .iter()
-                    .map(|fp| key_manager.get_public_cert(fp))
-                    .collect::<Result<Vec<_>>>()?;
+        if metadata.is_some() || !ops.is_empty() {
+            self.validate_metadata(&updated_metadata.template)?;
+        }
+
+        let format = updated_metadata.content_format();
+
+        match (content, &unlocked_key_pair) {
+            (Some(content), _) => {
+                SecretContent::parse(content, format)?;
             }
+            (None, Some(key_pair)) if format != previous_format => {
+                let current = self.decrypt_with_keypair(key_pair)?;
 
-            if content.is_none() {
-                updated_content =
-                    self.decrypt_with_keypair(&unlocked_key_pair.unwrap())?;
-            } else {
-                updated_metadata.modifications =
-                    updated_metadata.modifications.saturating_add(1);
+                SecretContent::parse(&current, format)?;
             }
+            _ => {}
+        }
 
+        updated_metadata.checksum_meta = String::new();
+        updated_metadata.updated_at = Utc::now();
+        updated_metadata.modifications =
+            updated_metadata.modifications.saturating_add(1);
+
+        let updated_recipient_certs = match fingerprints {
+            Some(fingerprints) => fingerprints
+                .iter()
+                .map(|fp| key_manager.get_public_cert(fp))
+                .collect::<Result<Vec<_>>>()?,
+            None => exsting_certificates,
+        };
+
+        if content.is_some() && password_changed {
+            updated_metadata.rotated_at = Some(updated_metadata.updated_at);
+        }
+
+        if content.is_some() || fingerprints.is_some() {
+            let updated_content = match content {
+                Some(content) => SecretString::from(content),
+                None => {
+                    self.decrypt_with_keypair(&unlocked_key_pair.unwrap())?
+                }
+            };
             let encrypted = self.encrypt_with_certs(
                 &updated_content,
                 &updated_recipient_certs,
             )?;
             let encrypted_str = String::from_utf8(encrypted.clone())
-                .context("Encrypted data is not valid UTF-8")?;
+                .crypto_context("Encrypted data is not valid UTF-8")?;
 
             updated_metadata.checksum_main = compute_checksum(&encrypted_str);
-            metadata_str = toml::to_string_pretty(&updated_metadata)
-                .context("Failed to serialize metadata")?;
-            updated_metadata.checksum_meta = compute_checksum(&metadata_str);
-            metadata_str = toml::to_string_pretty(&updated_metadata)
-                .context("Failed to serialize metadata")?;
 
             secure_write(&secret_path, encrypted)
-                .context("Failed to write encrypted secret file")?;
+                .io_context("Failed to write encrypted secret file")?;
         }
 
-        secure_write(&metadata_path, metadata_str)
-            .context("Failed to write updated metadata file")?;
+        sealed_fields.extend_from_slice(encrypt_fields);
+        updated_metadata = self.seal_metadata(
+            updated_metadata,
+            &sealed_fields,
+            &updated_recipient_certs,
+        )?;
+
+        self.write_metadata(&mut updated_metadata)?;
+
+        self.index.update(&self.config, &[&self.relative_path]);
 
         log::info!("Updated secret: {}", self.relative_path.display());
 
         Ok(self)
     }
 
+    /// Stores the next HOTP counter. Handing out a code is not an edit, so
+    /// unlike `update` this leaves `updated_at`, `modifications` and the
+    /// rotation bookkeeping alone.
+    pub fn set_otp_counter(&self, counter: u64, password: &str) -> Result<()> {
+        let key_manager = KeyManager::new(Arc::clone(&self.config));
+        let certs = self.recipient_certs(&key_manager)?;
+        let unlocker = MetadataUnlocker::new(
+            Arc::clone(&self.config),
+            SecretString::from(password),
+        );
+        let (mut metadata, sealed_fields) =
+            unlocker.unseal(&self.metadata()?)?;
+
+        metadata
+            .template
+            .extra
+            .get_or_insert_default()
+            .insert("counter".to_string(), TomlValue::Integer(counter as i64));
+        metadata = self.seal_metadata(metadata, &sealed_fields, &certs)?;
+
+        self.write_metadata(&mut metadata)?;
+        self.index.update(&self.config, &[&self.relative_path]);
+
+        Ok(())
+    }
+
     pub fn remove(&self, password: &str) -> Result<()> {
         let key_manager = KeyManager {
             config: Arc::clone(&self.config),
@@ -474,15 +598,15 @@ impl Secret {
         let exsting_certificates = self.recipient_certs(&key_manager)?;
 
         if exsting_certificates.is_empty() {
-            return Err(anyhow::anyhow!(
-                "No recipients found in encrypted secret"
+            return Err(Error::NoRecipients(
+                "No recipients found in encrypted secret".to_string(),
             ));
         }
 
         let mut unlocked_key_pair: Option<KeyPair> = None;
 
         for exsting_certificate in exsting_certificates {
-            let key_pair = self.unlock_keypair(&exsting_certificate, password);
+            let key_pair = unlock_keypair(&exsting_certificate, password);
 
             if key_pair.is_ok() {
                 unlocked_key_pair = key_pair.unwrap_or(None);
@@ -491,9 +615,7 @@ impl Secret {
         }
 
         if unlocked_key_pair.is_none() {
-            return Err(anyhow::anyhow!(
-                "Password could not unlock any recipient key"
-            ));
+            return Err(Error::InvalidPassword);
         }
 
         for path in [self.secret_path()?, self.metadata_path()?] {
@@ -503,15 +625,16 @@ impl Secret {
                     log::warn!("File not found: {}", path.display());
                 }
                 Err(e) => {
-                    return Err(anyhow::anyhow!(
-                        "Failed to remove {}: {}",
-                        path.display(),
-                        e
-                    ));
+                    return Err(Error::Io {
+                        context: format!("Failed to remove {}", path.display()),
+                        source: e,
+                    });
                 }
             }
         }
 
+        self.index.update(&self.config, &[&self.relative_path]);
+
         log::info!("Removed secret: {}", self.relative_path.display());
 
         Ok(())
@@ -522,25 +645,70 @@ impl Secret {
         let destination_secret = Secret {
             relative_path: destination,
             config: Arc::clone(&self.config),
+            index: Arc::clone(&self.index),
         };
         let current_secret_path = self.secret_path()?;
         let current_metadata_path = self.metadata_path()?;
         let dest_secret_path = destination_secret.secret_path()?;
         let dest_metadata_path = destination_secret.metadata_path()?;
 
+        if !current_secret_path.exists() || !current_metadata_path.exists() {
+            return Err(Error::NotFound(self.relative_path.clone()));
+        }
+        if dest_secret_path.exists() || dest_metadata_path.exists() {
+            return Err(Error::AlreadyExists(
+                destination_secret.relative_path.clone(),
+            ));
+        }
+
         if let Some(parent) = dest_secret_path.parent() {
             secure_create_dir_all(parent, &self.config.secrets_dir)
-                .context("Failed to create destination secret directory")?;
+                .io_context("Failed to create destination secret directory")?;
         }
         if let Some(parent) = dest_metadata_path.parent() {
             secure_create_dir_all(parent, &self.config.metadata_dir)
-                .context("Failed to create destination metadata directory")?;
+                .io_context(
+                    "Failed to create destination metadata directory",
+                )?;
         }
 
         fs::rename(&current_secret_path, &dest_secret_path)
-            .context("Failed to move secret file")?;
+            .io_context("Failed to move secret file")?;
         fs::rename(&current_metadata_path, &dest_metadata_path)
-            .context("Failed to move metadata file")?;
+            .io_context("Failed to move metadata file")?;
+
+        self.index.update(
+            &self.config,
+            &[&self.relative_path, &destination_secret.relative_path],
+        );
+
+        Ok(destination_secret)
+    }
+
+    pub fn clone_to(
+        &self,
+        destination: PathBuf,
+        fingerprints: &[&str],
+        password: &str,
+    ) -> Result<Secret> {
+        let content = self.plaintext_content(password)?;
+        let (metadata, sealed_fields) = MetadataUnlocker::new(
+            Arc::clone(&self.config),
+            SecretString::from(password),
+        )
+        .unseal(&self.metadata()?)?;
+        let destination_secret = Secret {
+            relative_path: destination,
+            config: Arc::clone(&self.config),
+            index: Arc::clone(&self.index),
+        };
+
+        destination_secret.create(
+            &content,
+            &metadata.to_base(),
+            &sealed_fields,
+            fingerprints,
+        )?;
 
         Ok(destination_secret)
     }
@@ -550,26 +718,167 @@ impl Secret {
         let destination_secret = Secret {
             relative_path: destination,
             config: Arc::clone(&self.config),
+            index: Arc::clone(&self.index),
         };
         let current_secret_path = self.secret_path()?;
         let current_metadata_path = self.metadata_path()?;
         let dest_secret_path = destination_secret.secret_path()?;
         let dest_metadata_path = destination_secret.metadata_path()?;
 
+        if !current_secret_path.exists() || !current_metadata_path.exists() {
+            return Err(Error::NotFound(self.relative_path.clone()));
+        }
+        if dest_secret_path.exists() || dest_metadata_path.exists() {
+            return Err(Error::AlreadyExists(
+                destination_secret.relative_path.clone(),
+            ));
+        }
+
         if let Some(parent) = dest_secret_path.parent() {
             secure_create_dir_all(parent, &self.config.secrets_dir)
-                .context("Failed to create destination secret directory")?;
+                .io_context("Failed to create destination secret directory")?;
         }
         if let Some(parent) = dest_metadata_path.parent() {
             secure_create_dir_all(parent, &self.config.metadata_dir)
-                .context("Failed to create destination metadata directory")?;
+                .io_context(
+                    "Failed to create destination metadata directory",
+                )?;
         }
 
         fs::copy(&current_secret_path, &dest_secret_path)
-            .context("Failed to copy secret file")?;
+            .io_context("Failed to copy secret file")?;
         fs::copy(&current_metadata_path, &dest_metadata_path)
-            .context("Failed to copy metadata file")?;
+            .io_context("Failed to copy metadata file")?;
+
+        self.index
+            .update(&self.config, &[&destination_secret.relative_path]);
 
         Ok(destination_secret)
     }
 }
+
+pub(crate) fn metadata_path(config: &Config, relative_path: &Path) -> PathBuf {
+    config
+        .metadata_dir
+        .join(relative_path)
+        .with_extension("meta.toml")
+}
+
+pub(crate) fn load_metadata(
+    config: &Config,
+    relative_path: &Path,
+) -> Result<(Metadata, String)> {
+    let metadata_path = metadata_path(config, relative_path);
+
+    if !metadata_path.exists() {
+        return Err(Error::NotFound(relative_path.to_path_buf()));
+    }
+
+    let text = fs::read_to_string(&metadata_path).io_context(format!(
+        "Failed to read metadata from {}",
+        metadata_path.display()
+    ))?;
+
+    let invalid = |reason: String| Error::InvalidMetadata {
+        path: relative_path.to_path_buf(),
+        reason,
+    };
+    let mut table: Table =
+        toml::from_str(&text).map_err(|e| invalid(e.to_string()))?;
+
+    migration::migrate(&mut table).map_err(invalid)?;
+
+    let metadata = TomlValue::Table(table)
+        .try_into()
+        .map_err(|e: toml::de::Error| invalid(e.to_string()))?;
+
+    Ok((metadata, text))
+}
+
+pub(crate) fn message_recipients(ciphertext: &str) -> Result<Vec<KeyID>> {
+    let message: Message = ciphertext
+        .parse()
+        .or_else(|_| Message::from_bytes(ciphertext.as_bytes()))
+        .crypto_context("Failed to parse secret as message")?;
+
+    Ok(message
+        .packets()
+        .descendants()
+        .filter_map(|pkt| match pkt {
+            Packet::PKESK(pkesk) => Some(pkesk.recipient().into()),
+            _ => None,
+        })
+        .collect())
+}
+
+pub(crate) fn unlock_keypair(
+    cert: &Cert,
+    password: &str,
+) -> Result<Option<KeyPair>> {
+    let policy = &StandardPolicy::new();
+
+    if let Some(key) = cert
+        .keys()
+        .secret()
+        .with_policy(policy, None)
+        .alive()
+        .revoked(false)
+        .for_storage_encryption()
+        .next()
+    {
+        let kp = key
+            .key()
+            .clone()
+            .parts_into_secret()
+            .crypto_context("Failed to get secret key parts")?
+            .decrypt_secret(&Password::from(password));
+
+        if let Ok(decrypted_secret) = kp {
+            return Ok(Some(
+                decrypted_secret
+                    .into_keypair()
+                    .crypto_context("Failed to build keypair")?,
+            ));
+        }
+    }
+
+    Ok(None)
+}
+
+pub(crate) fn decrypt_message(
+    ciphertext: &[u8],
+    keypair: &KeyPair,
+) -> Result<SecretString> {
+    let policy = &StandardPolicy::new();
+    let helper = DecryptHelper::new(keypair.clone());
+    let mut decryptor = DecryptorBuilder::from_bytes(ciphertext)
+        .crypto_context("Failed to create decryptor from ciphertext")?
+        .with_policy(policy, None, helper)
+        .crypto_context("Failed to configure decryptor with policy")?;
+    let mut plaintext = SecretBuffer::with_capacity(ciphertext.len());
+    let mut chunk = Zeroizing::new([0u8; DECRYPT_CHUNK_SIZE]);
+
+    loop {
+        let read = match decryptor.read(&mut *chunk) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                return Err(e)
+                    .crypto_context("Failed to decrypt content with keypair");
+            }
+        };
+
+        plaintext.extend_from_slice(&chunk[..read]);
+    }
+
+    plaintext.into_secret_string().ok_or_else(|| {
+        Error::Internal("Decrypted content is not valid UTF-8".to_string())
+    })
+}
+
+fn serialize_metadata(metadata: &Metadata) -> Result<String> {
+    toml::to_string_pretty(metadata).map_err(|e| {
+        Error::Internal(format!("Failed to serialize metadata: {e}"))
+    })
+}
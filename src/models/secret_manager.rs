@@ -4,30 +4,85 @@ use std::{
     sync::Arc,
 };
 
-use anyhow::Result;
-use sequoia_openpgp::{Cert, parse::Parse};
+use chrono::{DateTime, Duration, Utc};
 use serde::Serialize;
 use walkdir::WalkDir;
 
 use crate::{
-    models::{config::Config, metadata::Metadata, secret::Secret},
-    utils::checksum::compute_checksum,
+    error::{IoContext, Result},
+    models::{
+        audit::{PasswordAuditor, reuse_findings},
+        breach::BreachCorpus,
+        config::Config,
+        content::{PASSWORD_FIELD, SecretContent},
+        index::SharedIndex,
+        key_manager::KeyManager,
+        metadata::{DeadlineKind, Metadata},
+        migration::{METADATA_VERSION, stored_version},
+        saved_query::SavedQueries,
+        search::{SearchHit, score, tokenize},
+        secret::Secret,
+        unlock::{MetadataUnlocker, MetadataView},
+    },
+    utils::cancel::CancellationToken,
+    utils::checksum::compute_checksum_from_file,
     utils::fs::{is_secure_dir, is_secure_file},
+    utils::memory::SecretString,
 };
 
+#[derive(Debug, Clone, Serialize)]
+pub struct MigratedFile {
+    pub path: PathBuf,
+    pub from_version: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedMigration {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationReport {
+    pub dry_run: bool,
+    pub current_version: u32,
+    pub migrated: Vec<MigratedFile>,
+    pub failed: Vec<FailedMigration>,
+    /// Outdated files whose secret no longer matches `checksum_main`. They
+    /// are migrated with the stored checksum so `diagnose` keeps reporting
+    /// the mismatch.
+    pub checksum_mismatches: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpiringSecret {
+    pub path: PathBuf,
+    pub kind: DeadlineKind,
+    pub deadline: DateTime<Utc>,
+    pub expired: bool,
+}
+
+#[derive(Debug)]
+pub struct FindResult {
+    pub total: usize,
+    pub remaining: usize,
+    pub hits: Vec<(PathBuf, Metadata)>,
+}
+
 #[derive(Debug)]
 pub struct SecretManager {
     pub config: Arc<Config>,
+    pub index: Arc<SharedIndex>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum DiagnosticStatus {
     Warning,
     Error,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub enum IssueType {
     UnexpectedError,
     RougeFile,
@@ -38,6 +93,17 @@ pub enum IssueType {
     InvalidMetadata,
     InvalidTimestamps,
     MissingAttachment,
+    SchemaViolation,
+    OutdatedMetadataVersion,
+    SecretExpired,
+    RotationOverdue,
+    UnauditableSecret,
+    BreachedPassword,
+    ShortPassword,
+    CommonPassword,
+    WeakPassword,
+    ReusedPassword,
+    SimilarPassword,
     MetadataChecksumMismatch,
     ModificationCountMismatch,
     SecretPathMismatch,
@@ -45,7 +111,7 @@ pub enum IssueType {
     SecretFingerprintMismatch,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct DiagnosticResult {
     pub status: DiagnosticStatus,
     pub issue: IssueType,
@@ -53,96 +119,386 @@ pub struct DiagnosticResult {
 }
 
 impl SecretManager {
-    pub fn new(config: Arc<Config>) -> Self {
-        Self { config }
+    pub fn new(config: Arc<Config>, index: Arc<SharedIndex>) -> Self {
+        Self { config, index }
     }
 
-    pub fn find<F, C>(
+    pub fn find<F, C, A>(
         &self,
         filter: Option<F>,
         mut sort: Option<C>,
+        after: Option<A>,
         offset: Option<usize>,
         limit: Option<usize>,
-    ) -> Result<Vec<PathBuf>>
+        cancel: &CancellationToken,
+    ) -> Result<FindResult>
     where
         F: Fn(&Metadata) -> bool,
         C: FnMut(&Metadata, &Metadata) -> Ordering,
+        A: Fn(&Path, &Metadata) -> bool,
     {
+        let index = self.index.snapshot(&self.config)?;
         let mut results = Vec::new();
 
-        for entry in WalkDir::new(&self.config.metadata_dir)
-            .into_iter()
-            .filter_map(Result::ok)
-            .filter(|e| {
-                e.file_type().is_file()
-                    && e.path()
-                        .file_name()
-                        .and_then(|n| n.to_str())
-                        .map(|name| name.ends_with(".meta.toml"))
-                        .unwrap_or(false)
-            })
-        {
-            let full_path = entry.path();
-            let relative_path =
-                match full_path.strip_prefix(&self.config.metadata_dir) {
-                    Ok(r) => r,
-                    Err(_) => continue,
-                };
+        for (relative_path, entry) in &index.entries {
+            cancel.check()?;
 
-            let file_stem = match relative_path
-                .file_name()
-                .and_then(|f| f.to_str())
-                .and_then(|f| f.strip_suffix(".meta.toml"))
+            if let Some(ref filter_fn) = filter
+                && !filter_fn(&entry.metadata)
             {
-                Some(stem) => stem,
-                None => continue,
+                continue;
+            }
+
+            results.push((relative_path, &entry.metadata));
+        }
+
+        let total = results.len();
+
+        if let Some(ref after_fn) = after {
+            results.retain(|(relative_path, metadata)| {
+                after_fn(relative_path, metadata)
+            });
+        }
+
+        let remaining = results.len();
+        let start = offset.unwrap_or(0).min(remaining);
+        let end = limit.map_or(remaining, |l| (start + l).min(remaining));
+        let mut compare =
+            |(a_path, a): &(&PathBuf, &Metadata),
+             (b_path, b): &(&PathBuf, &Metadata)| {
+                sort.as_mut()
+                    .map_or(Ordering::Equal, |cmp_fn| cmp_fn(a, b))
+                    .then_with(|| a_path.cmp(b_path))
             };
 
-            let mut base = relative_path.to_path_buf();
+        if end < remaining {
+            results.select_nth_unstable_by(end, &mut compare);
+            results.truncate(end);
+        }
+
+        results.sort_by(&mut compare);
+
+        let hits = results[start..end]
+            .iter()
+            .map(|(relative_path, metadata)| {
+                (relative_path.to_path_buf(), (*metadata).clone())
+            })
+            .collect();
+
+        Ok(FindResult {
+            total,
+            remaining,
+            hits,
+        })
+    }
+
+    pub fn search<F>(
+        &self,
+        text: &str,
+        filter: Option<F>,
+        fuzzy: bool,
+        limit: Option<usize>,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<SearchHit>>
+    where
+        F: Fn(&Metadata) -> bool,
+    {
+        let index = self.index.snapshot(&self.config)?;
+        let query = tokenize(text);
+        let mut hits = Vec::new();
+
+        if query.is_empty() {
+            return Ok(hits);
+        }
+
+        for (relative_path, entry) in &index.entries {
+            cancel.check()?;
 
-            base.set_file_name(file_stem);
+            if let Some(ref filter_fn) = filter
+                && !filter_fn(&entry.metadata)
+            {
+                continue;
+            }
+
+            if let Some(score) = score(&query, &entry.terms, fuzzy) {
+                hits.push(SearchHit {
+                    path: relative_path.clone(),
+                    score,
+                });
+            }
+        }
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+        if let Some(limit) = limit {
+            hits.truncate(limit);
+        }
+
+        Ok(hits)
+    }
+
+    pub fn expiring<F>(
+        &self,
+        within: Duration,
+        filter: Option<F>,
+        view: &MetadataView,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<ExpiringSecret>>
+    where
+        F: Fn(&Metadata) -> bool,
+    {
+        let index = self.index.snapshot(&self.config)?;
+        let now = Utc::now();
+        let horizon = now.checked_add_signed(within).unwrap_or(now);
+        let mut expiring = Vec::new();
+
+        for (relative_path, entry) in &index.entries {
+            cancel.check()?;
+
+            // `expires_at` and `rotate_every` may be sealed, so the deadline
+            // is read from the same view the filter sees.
+            let deadline =
+                view.with(&entry.metadata, |metadata| match filter {
+                    Some(ref filter_fn) if !filter_fn(metadata) => None,
+                    _ => metadata.deadline(),
+                });
+
+            if let Some((kind, deadline)) = deadline
+                && deadline <= horizon
+            {
+                expiring.push(ExpiringSecret {
+                    path: relative_path.clone(),
+                    kind,
+                    deadline,
+                    expired: deadline <= now,
+                });
+            }
+        }
+
+        expiring.sort_by(|a, b| {
+            a.deadline
+                .cmp(&b.deadline)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+
+        Ok(expiring)
+    }
+
+    pub fn audit<F>(
+        &self,
+        password: SecretString,
+        filter: Option<F>,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<DiagnosticResult>>
+    where
+        F: Fn(&Metadata) -> bool,
+    {
+        let index = self.index.snapshot(&self.config)?;
+        let unlocker =
+            MetadataUnlocker::new(Arc::clone(&self.config), password);
+        let auditor = PasswordAuditor::new();
+        let mut corpus = self
+            .config
+            .breach_corpus
+            .as_ref()
+            .map(BreachCorpus::open)
+            .transpose()?;
+        let mut findings = Vec::new();
+        let mut passwords = Vec::new();
+
+        for (relative_path, entry) in &index.entries {
+            cancel.check()?;
+
+            if let Some(ref filter_fn) = filter
+                && !filter_fn(&entry.metadata)
+            {
+                continue;
+            }
+
+            // One-time password seeds are random keys, not passwords.
+            if matches!(
+                entry.metadata.template.r#type.as_deref(),
+                Some("totp" | "hotp")
+            ) {
+                continue;
+            }
 
             let secret = Secret {
-                relative_path: base,
+                relative_path: relative_path.clone(),
                 config: Arc::clone(&self.config),
+                index: Arc::clone(&self.index),
             };
+            let result = secret
+                .secret_path()
+                .and_then(|path| {
+                    std::fs::read_to_string(&path).io_context(format!(
+                        "Failed to read secret file {}",
+                        path.display()
+                    ))
+                })
+                .and_then(|message| unlocker.decrypt(&message))
+                .and_then(|content| {
+                    SecretContent::parse(
+                        &content,
+                        entry.metadata.content_format(),
+                    )?
+                    .get(PASSWORD_FIELD)
+                });
 
-            let metadata = match secret.metadata() {
-                Ok(m) => m,
-                Err(_) => continue,
-            };
+            match result {
+                Ok(password) if password.is_empty() => {}
+                Ok(password) => {
+                    findings.extend(auditor.findings(relative_path, &password));
 
-            if let Some(ref filter_fn) = filter {
-                if !filter_fn(&metadata) {
-                    continue;
+                    if let Some(corpus) = corpus.as_mut()
+                        && let Some(count) = corpus.lookup(&password)?
+                    {
+                        findings.push(DiagnosticResult {
+                            status: DiagnosticStatus::Error,
+                            issue: IssueType::BreachedPassword,
+                            message: format!(
+                                "Secret '{}' has a password found {} times in known breaches",
+                                relative_path.display(),
+                                count
+                            ),
+                        });
+                    }
+
+                    passwords.push((relative_path.clone(), password));
                 }
+                Err(e) => findings.push(DiagnosticResult {
+                    status: DiagnosticStatus::Warning,
+                    issue: IssueType::UnauditableSecret,
+                    message: format!(
+                        "Secret '{}' could not be audited: {}",
+                        relative_path.display(),
+                        e
+                    ),
+                }),
             }
-
-            results.push(metadata);
         }
 
-        if let Some(ref mut cmp_fn) = sort {
-            results.sort_by(|a, b| cmp_fn(a, b));
+        findings.extend(reuse_findings(&passwords, cancel)?);
+
+        log::info!(
+            "Audited {} secrets with {} findings",
+            passwords.len(),
+            findings.len()
+        );
+
+        Ok(findings)
+    }
+
+    pub fn migrate(
+        &self,
+        dry_run: bool,
+        cancel: &CancellationToken,
+    ) -> Result<MigrationReport> {
+        let mut report = MigrationReport {
+            dry_run,
+            current_version: METADATA_VERSION,
+            migrated: Vec::new(),
+            failed: Vec::new(),
+            checksum_mismatches: Vec::new(),
+        };
+
+        for entry in WalkDir::new(&self.config.metadata_dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+        {
+            cancel.check()?;
+
+            let Some(relative_path) = entry
+                .path()
+                .strip_prefix(&self.config.metadata_dir)
+                .ok()
+                .and_then(|r| r.to_str())
+                .and_then(|r| r.strip_suffix(".meta.toml"))
+                .map(PathBuf::from)
+            else {
+                continue;
+            };
+            let secret = Secret {
+                relative_path: relative_path.clone(),
+                config: Arc::clone(&self.config),
+                index: Arc::clone(&self.index),
+            };
+            let result =
+                secret.read_metadata().and_then(|(mut metadata, text)| {
+                    let from_version = stored_version(&text);
+
+                    if from_version == METADATA_VERSION {
+                        return Ok((from_version, false));
+                    }
+
+                    let secret_path = secret.secret_path()?;
+                    let mismatch = secret_path.exists()
+                        && compute_checksum_from_file(&secret_path)?
+                            != metadata.checksum_main;
+
+                    if !dry_run {
+                        secret.write_metadata(&mut metadata)?;
+                    }
+
+                    Ok((from_version, mismatch))
+                });
+
+            match result {
+                Ok((from_version, mismatch))
+                    if from_version < METADATA_VERSION =>
+                {
+                    if mismatch {
+                        log::warn!(
+                            "Secret '{}' does not match its checksum, keeping the stored one",
+                            relative_path.display()
+                        );
+                        report.checksum_mismatches.push(relative_path.clone());
+                    }
+
+                    report.migrated.push(MigratedFile {
+                        path: relative_path,
+                        from_version,
+                    });
+                }
+                Ok(_) => {}
+                Err(e) => report.failed.push(FailedMigration {
+                    path: relative_path,
+                    reason: e.to_string(),
+                }),
+            }
         }
 
-        let total = results.len();
-        let start = offset.unwrap_or(0).min(total);
-        let end = limit.map_or(total, |l| (start + l).min(total));
+        if !dry_run && !report.migrated.is_empty() {
+            let paths: Vec<&Path> =
+                report.migrated.iter().map(|m| m.path.as_path()).collect();
 
-        let response = results[start..end]
-            .iter()
-            .map(|meta| meta.path.clone())
-            .collect();
+            self.index.update(&self.config, &paths);
+
+            log::info!(
+                "Migrated {} metadata files to version {}",
+                report.migrated.len(),
+                METADATA_VERSION
+            );
+        }
 
-        Ok(response)
+        Ok(report)
     }
 
-    pub fn diagnose(&self) -> Result<Vec<DiagnosticResult>> {
+    pub fn reindex(&self, cancel: &CancellationToken) -> Result<usize> {
+        self.index.rebuild(&self.config, cancel)
+    }
+
+    pub fn diagnose(
+        &self,
+        cancel: &CancellationToken,
+    ) -> Result<Vec<DiagnosticResult>> {
         let mut diagnostics = Vec::new();
+        let key_manager = KeyManager::new(Arc::clone(&self.config));
 
         for dir_entry in WalkDir::new(&self.config.metadata_dir)
             .into_iter()
-            .filter_map(Result::ok)
+            .filter_map(|e| e.ok())
             .filter(|e| e.file_type().is_dir())
         {
             let dir_path = dir_entry.path();
@@ -160,10 +516,15 @@ impl SecretManager {
             }
 
             for file_entry in std::fs::read_dir(dir_path)
-                .unwrap()
-                .filter_map(Result::ok)
+                .io_context(format!(
+                    "Failed to read directory {}",
+                    dir_path.display()
+                ))?
+                .filter_map(|e| e.ok())
                 .filter(|e| e.path().is_file())
             {
+                cancel.check()?;
+
                 let file_path = file_entry.path();
 
                 if !is_secure_file(&file_path) {
@@ -178,6 +539,10 @@ impl SecretManager {
                     });
                 }
 
+                if SavedQueries::is_queries_file(&self.config, &file_path) {
+                    continue;
+                }
+
                 let file_name = file_path
                     .file_name()
                     .and_then(|n| n.to_str())
@@ -194,6 +559,7 @@ impl SecretManager {
                 let secret = Secret {
                     relative_path: relative_path.clone(),
                     config: Arc::clone(&self.config),
+                    index: Arc::clone(&self.index),
                 };
 
                 if self.config.metadata_dir == self.config.secrets_dir {
@@ -239,8 +605,8 @@ impl SecretManager {
                     }
                 }
 
-                let mut metadata = match secret.metadata() {
-                    Ok(m) => m,
+                let (metadata, text) = match secret.read_metadata() {
+                    Ok(read) => read,
                     Err(_) => {
                         diagnostics.push(DiagnosticResult {
                             status: DiagnosticStatus::Error,
@@ -286,6 +652,56 @@ impl SecretManager {
                     });
                 }
 
+                // Sealed fields cannot be checked without a password, so
+                // schemas are only enforced on plaintext metadata here.
+                let violations = match metadata.encrypted {
+                    Some(_) => Vec::new(),
+                    None => self.config.schemas.violations(&metadata.template),
+                };
+
+                for violation in violations {
+                    diagnostics.push(DiagnosticResult {
+                        status: DiagnosticStatus::Error,
+                        issue: IssueType::SchemaViolation,
+                        message: format!(
+                            "Metadata '{}' field '{}' {}",
+                            file_path.display(),
+                            violation.field,
+                            violation.reason
+                        ),
+                    });
+                }
+
+                let now = Utc::now();
+
+                if let Some(expires_at) = metadata.template.expires_at
+                    && expires_at <= now
+                {
+                    diagnostics.push(DiagnosticResult {
+                        status: DiagnosticStatus::Warning,
+                        issue: IssueType::SecretExpired,
+                        message: format!(
+                            "Secret '{}' expired at {}",
+                            metadata.path.display(),
+                            expires_at.to_rfc3339()
+                        ),
+                    });
+                }
+
+                if let Some(due) = metadata.rotation_due()
+                    && due <= now
+                {
+                    diagnostics.push(DiagnosticResult {
+                        status: DiagnosticStatus::Warning,
+                        issue: IssueType::RotationOverdue,
+                        message: format!(
+                            "Secret '{}' was due for rotation at {}",
+                            metadata.path.display(),
+                            due.to_rfc3339()
+                        ),
+                    });
+                }
+
                 let time_diff =
                     (metadata.updated_at - metadata.created_at).num_seconds();
 
@@ -328,42 +744,56 @@ impl SecretManager {
                     continue;
                 }
 
-                if metadata.fingerprint
-                    != Cert::from_reader(
-                        secret.plaintext_content()?.as_bytes(),
-                    )?
-                    .fingerprint()
-                    .to_hex()
-                    .to_uppercase()
-                {
-                    diagnostics.push(DiagnosticResult {
-                        status: DiagnosticStatus::Error,
-                        issue: IssueType::SecretFingerprintMismatch,
-                        message: "Secret fingerprint does not match metadata fingerprint '{}'"
-                            .into(),
-                    });
-                }
-
-                if let Ok(plaintext) = secret.plaintext_content() {
-                    let computed_checksum =
-                        compute_checksum_from_content(&plaintext);
-                    if computed_checksum != metadata.checksum_main {
+                match secret.recipient_certs(&key_manager) {
+                    Ok(certs) if certs.is_empty() => {
+                        diagnostics.push(DiagnosticResult {
+                            status: DiagnosticStatus::Warning,
+                            issue: IssueType::SecretFingerprintMismatch,
+                            message: format!(
+                                "Secret '{}' is not encrypted to any key in the keys directory",
+                                secret.relative_path.display(),
+                            ),
+                        });
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
                         diagnostics.push(DiagnosticResult {
                             status: DiagnosticStatus::Error,
-                            issue: IssueType::SecretChecksumMismatch,
+                            issue: IssueType::SecretFingerprintMismatch,
                             message: format!(
-                                "Secret checksum mismatch for '{}'",
-                                file_path.display(),
+                                "Failed to read recipients of '{}': {}",
+                                secret.relative_path.display(),
+                                e
                             ),
                         });
                     }
                 }
 
-                metadata.checksum_meta = "".to_string();
+                if compute_checksum_from_file(&secret.secret_path()?)
+                    .is_ok_and(|checksum| checksum != metadata.checksum_main)
+                {
+                    diagnostics.push(DiagnosticResult {
+                        status: DiagnosticStatus::Error,
+                        issue: IssueType::SecretChecksumMismatch,
+                        message: format!(
+                            "Secret checksum mismatch for '{}'",
+                            file_path.display(),
+                        ),
+                    });
+                }
 
-                if compute_checksum_from_content(
-                    &toml::to_string(&metadata).unwrap(),
-                ) != metadata.checksum_meta
+                if stored_version(&text) < METADATA_VERSION {
+                    diagnostics.push(DiagnosticResult {
+                        status: DiagnosticStatus::Warning,
+                        issue: IssueType::OutdatedMetadataVersion,
+                        message: format!(
+                            "Metadata '{}' uses an older format, run 'migrate' to upgrade it",
+                            file_path.display(),
+                        ),
+                    });
+                } else if metadata
+                    .compute_checksum_meta()
+                    .is_ok_and(|checksum| checksum != metadata.checksum_meta)
                 {
                     diagnostics.push(DiagnosticResult {
                         status: DiagnosticStatus::Error,
@@ -380,7 +810,7 @@ impl SecretManager {
         if self.config.metadata_dir != self.config.secrets_dir {
             for dir_entry in WalkDir::new(&self.config.secrets_dir)
                 .into_iter()
-                .filter_map(Result::ok)
+                .filter_map(|e| e.ok())
                 .filter(|e| e.file_type().is_dir())
             {
                 let dir_path = dir_entry.path();
@@ -397,10 +827,15 @@ impl SecretManager {
                 }
 
                 for file_entry in std::fs::read_dir(dir_path)
-                    .unwrap()
-                    .filter_map(Result::ok)
+                    .io_context(format!(
+                        "Failed to read directory {}",
+                        dir_path.display()
+                    ))?
+                    .filter_map(|e| e.ok())
                     .filter(|e| e.path().is_file())
                 {
+                    cancel.check()?;
+
                     let file_path = file_entry.path();
 
                     if !is_secure_file(&file_path) {
@@ -444,6 +879,7 @@ impl SecretManager {
                     let secret = Secret {
                         relative_path,
                         config: Arc::clone(&self.config),
+                        index: Arc::clone(&self.index),
                     };
 
                     if !secret.metadata_path()?.exists() {
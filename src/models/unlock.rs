@@ -0,0 +1,153 @@
+use std::{
+    cell::RefCell, collections::HashMap, path::PathBuf, rc::Rc, sync::Arc,
+};
+
+use sequoia_openpgp::{KeyID, crypto::KeyPair};
+use toml::Table;
+
+use crate::{
+    error::{Error, Result},
+    models::{
+        config::Config,
+        key_manager::KeyManager,
+        metadata::Metadata,
+        secret::{decrypt_message, message_recipients, unlock_keypair},
+    },
+    utils::memory::SecretString,
+};
+
+pub struct MetadataUnlocker {
+    key_manager: KeyManager,
+    password: SecretString,
+    keypairs: RefCell<HashMap<KeyID, Option<KeyPair>>>,
+}
+
+impl MetadataUnlocker {
+    pub fn new(config: Arc<Config>, password: SecretString) -> Self {
+        Self {
+            key_manager: KeyManager::new(config),
+            password,
+            keypairs: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn keypair(&self, keyid: &KeyID) -> Option<KeyPair> {
+        self.keypairs
+            .borrow_mut()
+            .entry(keyid.clone())
+            .or_insert_with(|| {
+                let cert = self.key_manager.find_cert_by_keyid(keyid)?;
+
+                unlock_keypair(&cert, &self.password).ok().flatten()
+            })
+            .clone()
+    }
+
+    pub fn decrypt(&self, message: &str) -> Result<SecretString> {
+        for keyid in message_recipients(message)? {
+            if let Some(keypair) = self.keypair(&keyid) {
+                return decrypt_message(message.as_bytes(), &keypair);
+            }
+        }
+
+        Err(Error::InvalidPassword)
+    }
+
+    pub fn unseal(
+        &self,
+        metadata: &Metadata,
+    ) -> Result<(Metadata, Vec<String>)> {
+        let Some(encrypted) = &metadata.encrypted else {
+            return Ok((metadata.clone(), Vec::new()));
+        };
+
+        let text = self.decrypt(encrypted)?;
+        let sealed: Table =
+            toml::from_str(&text).map_err(|e| Error::InvalidMetadata {
+                path: metadata.path.clone(),
+                reason: format!("Failed to parse encrypted fields: {e}"),
+            })?;
+        let keys = sealed.keys().cloned().collect();
+
+        Ok((metadata.unseal(sealed)?, keys))
+    }
+
+    pub fn unlock(&self, metadata: &Metadata) -> Result<Metadata> {
+        self.unseal(metadata).map(|(metadata, _)| metadata)
+    }
+}
+
+/// Metadata as a single request sees it. With a password, sealed fields are
+/// opened once per secret; entries the password cannot open are seen as
+/// stored, so their sealed fields are simply missing.
+pub struct MetadataView {
+    unlocker: Option<MetadataUnlocker>,
+    unlocked: RefCell<HashMap<PathBuf, Option<Rc<Metadata>>>>,
+    error: RefCell<Option<Error>>,
+}
+
+impl MetadataView {
+    pub fn new(config: Arc<Config>, password: Option<SecretString>) -> Self {
+        Self {
+            unlocker: password
+                .map(|password| MetadataUnlocker::new(config, password)),
+            unlocked: RefCell::new(HashMap::new()),
+            error: RefCell::new(None),
+        }
+    }
+
+    pub fn with<R>(
+        &self,
+        metadata: &Metadata,
+        f: impl FnOnce(&Metadata) -> R,
+    ) -> R {
+        let Some(unlocker) = &self.unlocker else {
+            return f(metadata);
+        };
+
+        if metadata.encrypted.is_none() {
+            return f(metadata);
+        }
+
+        let cached = self.unlocked.borrow().get(&metadata.path).cloned();
+        let unlocked = cached.unwrap_or_else(|| {
+            let unlocked = match unlocker.unlock(metadata) {
+                Ok(unlocked) => Some(Rc::new(unlocked)),
+                Err(e) => {
+                    log::debug!(
+                        "Failed to unlock metadata of '{}': {}",
+                        metadata.path.display(),
+                        e
+                    );
+                    self.error.borrow_mut().get_or_insert(e);
+
+                    None
+                }
+            };
+
+            self.unlocked
+                .borrow_mut()
+                .insert(metadata.path.clone(), unlocked.clone());
+
+            unlocked
+        });
+
+        match unlocked {
+            Some(unlocked) => f(&unlocked),
+            None => f(metadata),
+        }
+    }
+
+    /// Fails when the password opened none of the sealed entries the request
+    /// looked at, which almost always means it is the wrong password.
+    pub fn check(&self) -> Result<()> {
+        if self.unlocked.borrow().values().any(Option::is_some) {
+            return Ok(());
+        }
+
+        match self.error.borrow_mut().take() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,525 @@
+use std::{
+    collections::{BTreeSet, VecDeque},
+    path::{Path, PathBuf},
+    sync::{
+        Arc, Mutex, PoisonError,
+        mpsc::{self, Receiver, RecvTimeoutError},
+    },
+    thread,
+    time::Duration,
+};
+
+use notify::{
+    Event, RecommendedWatcher, RecursiveMode, Watcher as NotifyWatcher,
+};
+use tokio::sync::RwLock;
+
+use crate::{
+    error::{Error, Result},
+    models::{
+        config::Config,
+        index::SharedIndex,
+        migration::{METADATA_VERSION, stored_version},
+        saved_query::SavedQueries,
+        secret::Secret,
+        secret_manager::{DiagnosticResult, DiagnosticStatus, IssueType},
+    },
+    utils::checksum::compute_checksum_from_file,
+};
+
+const DEBOUNCE: Duration = Duration::from_millis(250);
+const MAX_DIAGNOSTICS: usize = 256;
+
+/// Diagnostics found by the watcher, numbered so that `diagnose` can drop
+/// exactly the ones it reported once its response is ready.
+#[derive(Default)]
+struct Found {
+    next: u64,
+    results: VecDeque<(u64, DiagnosticResult)>,
+}
+
+pub struct VaultWatcher {
+    _watcher: RecommendedWatcher,
+    index: Arc<SharedIndex>,
+    diagnostics: Arc<Mutex<Found>>,
+}
+
+impl VaultWatcher {
+    pub fn start(
+        config: Arc<Config>,
+        index: Arc<SharedIndex>,
+        lock: Arc<RwLock<()>>,
+    ) -> Result<Self> {
+        let (sender, receiver) = mpsc::channel();
+        let events_index = Arc::clone(&index);
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<Event>| {
+                match event {
+                    Ok(event) => {
+                        if event.need_rescan() {
+                            events_index.mark_unverified();
+                        }
+
+                        for path in event.paths {
+                            let _ = sender.send(path);
+                        }
+                    }
+                    Err(e) => {
+                        log::warn!("Vault watcher error: {}", e);
+
+                        events_index.mark_unverified();
+                    }
+                }
+            })
+            .map_err(|e| {
+                Error::Internal(format!("Failed to create vault watcher: {e}"))
+            })?;
+
+        // Watching the base directory also covers vault directories that
+        // are only created later; ones configured elsewhere are watched on
+        // their own.
+        let dirs = [&config.secrets_dir, &config.metadata_dir]
+            .into_iter()
+            .filter(|dir| !dir.starts_with(&config.base_dir));
+
+        for dir in [&config.base_dir].into_iter().chain(dirs) {
+            if !dir.exists() {
+                log::warn!(
+                    "Not watching missing vault directory {}",
+                    dir.display()
+                );
+
+                continue;
+            }
+
+            watcher.watch(dir, RecursiveMode::Recursive).map_err(|e| {
+                Error::Internal(format!(
+                    "Failed to watch {}: {e}",
+                    dir.display()
+                ))
+            })?;
+        }
+
+        let diagnostics = Arc::new(Mutex::new(Found::default()));
+        let worker_diagnostics = Arc::clone(&diagnostics);
+        let worker_index = Arc::clone(&index);
+
+        thread::Builder::new()
+            .name("passd-watcher".to_string())
+            .spawn(move || {
+                watch_loop(
+                    &config,
+                    &worker_index,
+                    &lock,
+                    &receiver,
+                    &worker_diagnostics,
+                )
+            })
+            .map_err(|e| {
+                Error::Internal(format!("Failed to spawn vault watcher: {e}"))
+            })?;
+
+        index.set_watched(true);
+
+        log::info!("Watching vault for out-of-band changes");
+
+        Ok(Self {
+            _watcher: watcher,
+            index,
+            diagnostics,
+        })
+    }
+
+    /// The diagnostics not yet acknowledged, and the mark to acknowledge
+    /// them with once they were reported.
+    pub fn diagnostics(&self) -> (Vec<DiagnosticResult>, u64) {
+        let found = self
+            .diagnostics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let results = found
+            .results
+            .iter()
+            .map(|(_, result)| result.clone())
+            .collect();
+
+        (results, found.next)
+    }
+
+    /// Drops the diagnostics found before `mark` was handed out.
+    pub fn acknowledge(&self, mark: u64) {
+        self.diagnostics
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .results
+            .retain(|(number, _)| *number >= mark);
+    }
+}
+
+impl Drop for VaultWatcher {
+    fn drop(&mut self) {
+        self.index.set_watched(false);
+    }
+}
+
+fn watch_loop(
+    config: &Arc<Config>,
+    index: &Arc<SharedIndex>,
+    lock: &RwLock<()>,
+    receiver: &Receiver<PathBuf>,
+    diagnostics: &Mutex<Found>,
+) {
+    while let Ok(first) = receiver.recv() {
+        let mut changed = BTreeSet::new();
+        let mut directories = BTreeSet::new();
+        let mut collect = |path: &Path| match classify(config, path) {
+            Some(Change::Secret(path)) => changed.insert(path),
+            Some(Change::Directory(path)) => directories.insert(path),
+            None => false,
+        };
+
+        collect(&first);
+
+        loop {
+            match receiver.recv_timeout(DEBOUNCE) {
+                Ok(path) => collect(&path),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            };
+        }
+
+        // Moving or removing a directory only reports the directory itself,
+        // so everything indexed or stored below it has to be looked at.
+        for directory in &directories {
+            changed.extend(index.paths_under(config, directory));
+        }
+
+        // passd's own writes raise events as well, but are indexed by the
+        // time they are seen. Only paths the index disagrees with are worth
+        // blocking readers for.
+        changed.retain(|path| !index.is_current(config, path));
+
+        if changed.is_empty() {
+            continue;
+        }
+
+        let _exclusive = lock.blocking_write();
+
+        // A write that was still running when its events arrived has
+        // finished once the lock is held.
+        changed.retain(|path| !index.is_current(config, path));
+
+        if changed.is_empty() {
+            continue;
+        }
+
+        for path in &changed {
+            log::info!(
+                "Secret '{}' changed outside passd, re-indexing it",
+                path.display()
+            );
+        }
+
+        let paths: Vec<&Path> = changed.iter().map(PathBuf::as_path).collect();
+
+        index.update(config, &paths);
+
+        let found: Vec<DiagnosticResult> = changed
+            .iter()
+            .filter_map(|path| verify_checksums(config, index, path))
+            .collect();
+
+        if found.is_empty() {
+            continue;
+        }
+
+        let mut diagnostics =
+            diagnostics.lock().unwrap_or_else(PoisonError::into_inner);
+
+        for result in found {
+            // A file that keeps changing stays reported once until the
+            // warning was acknowledged.
+            if diagnostics
+                .results
+                .iter()
+                .any(|(_, pending)| pending.message == result.message)
+            {
+                continue;
+            }
+
+            let number = diagnostics.next;
+
+            diagnostics.next += 1;
+            diagnostics.results.push_back((number, result));
+        }
+
+        let overflow =
+            diagnostics.results.len().saturating_sub(MAX_DIAGNOSTICS);
+
+        diagnostics.results.drain(..overflow);
+    }
+}
+
+enum Change {
+    Secret(PathBuf),
+    Directory(PathBuf),
+}
+
+fn classify(config: &Config, path: &Path) -> Option<Change> {
+    let (root, suffix) = if path.starts_with(&config.metadata_dir) {
+        (&config.metadata_dir, ".meta.toml")
+    } else if path.starts_with(&config.secrets_dir) {
+        (&config.secrets_dir, ".pgp")
+    } else {
+        return None;
+    };
+    let relative_path = path.strip_prefix(root).ok()?;
+
+    if SavedQueries::is_queries_file(config, path) {
+        return None;
+    }
+
+    if let Some(secret) = relative_path.to_str()?.strip_suffix(suffix) {
+        return Some(Change::Secret(PathBuf::from(secret)));
+    }
+
+    // A path that is gone can no longer be told apart from a directory;
+    // treating it as one is harmless when nothing was stored below it.
+    (path.is_dir() || !path.exists())
+        .then(|| Change::Directory(relative_path.to_path_buf()))
+}
+
+fn verify_checksums(
+    config: &Arc<Config>,
+    index: &Arc<SharedIndex>,
+    relative_path: &Path,
+) -> Option<DiagnosticResult> {
+    let secret = Secret {
+        relative_path: relative_path.to_path_buf(),
+        config: Arc::clone(config),
+        index: Arc::clone(index),
+    };
+    let secret_path = secret.secret_path().ok()?;
+
+    if !secret_path.exists() {
+        return None;
+    }
+
+    let (metadata, text) = secret.read_metadata().ok()?;
+    let current = stored_version(&text) == METADATA_VERSION;
+
+    let (issue, target) = if current
+        && metadata.compute_checksum_meta().ok()? != metadata.checksum_meta
+    {
+        (IssueType::MetadataChecksumMismatch, "Metadata")
+    } else if compute_checksum_from_file(&secret_path).ok()?
+        != metadata.checksum_main
+    {
+        (IssueType::SecretChecksumMismatch, "Secret")
+    } else {
+        return None;
+    };
+
+    let diagnostic = DiagnosticResult {
+        status: DiagnosticStatus::Warning,
+        issue,
+        message: format!(
+            "{} '{}' was changed outside passd and no longer matches its checksum",
+            target,
+            relative_path.display()
+        ),
+    };
+
+    log::warn!("{}", diagnostic.message);
+
+    Some(diagnostic)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, time::Instant};
+
+    use super::*;
+    use crate::models::metadata::Metadata;
+
+    fn write_metadata(config: &Config, relative_path: &str) {
+        let metadata = Metadata {
+            path: PathBuf::from(relative_path),
+            ..Metadata::default()
+        };
+        let path = config
+            .metadata_dir
+            .join(relative_path)
+            .with_extension("meta.toml");
+
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, toml::to_string_pretty(&metadata).unwrap()).unwrap();
+    }
+
+    fn indexed(config: &Arc<Config>, index: &SharedIndex) -> Vec<String> {
+        index
+            .snapshot(config)
+            .unwrap()
+            .entries
+            .keys()
+            .map(|path| path.display().to_string())
+            .collect()
+    }
+
+    fn config(dir: &Path) -> Arc<Config> {
+        Arc::new(Config {
+            base_dir: dir.to_path_buf(),
+            secrets_dir: dir.join("secrets"),
+            metadata_dir: dir.join(".metadata"),
+            keys_dir: dir.join(".keys"),
+            ..Config::default()
+        })
+    }
+
+    fn wait_for(config: &Arc<Config>, index: &SharedIndex, expected: &[&str]) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        while indexed(config, index) != expected && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        assert_eq!(indexed(config, index), expected);
+    }
+
+    #[test]
+    fn moving_a_directory_reindexes_its_secrets() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+
+        fs::create_dir_all(&config.secrets_dir).unwrap();
+        write_metadata(&config, "work/mail");
+        write_metadata(&config, "work/vpn");
+
+        let index = Arc::new(SharedIndex::new());
+
+        assert_eq!(indexed(&config, &index), ["work/mail", "work/vpn"]);
+
+        let _watcher = VaultWatcher::start(
+            Arc::clone(&config),
+            Arc::clone(&index),
+            Arc::new(RwLock::new(())),
+        )
+        .unwrap();
+
+        // The first read after startup checks the files itself.
+        assert_eq!(indexed(&config, &index), ["work/mail", "work/vpn"]);
+
+        fs::rename(
+            config.metadata_dir.join("work"),
+            config.metadata_dir.join("archive"),
+        )
+        .unwrap();
+
+        // Reads trust the index while the watcher runs, so the moved ones
+        // can only show up if the watcher indexed the new directory.
+        wait_for(&config, &index, &["archive/mail", "archive/vpn"]);
+    }
+
+    #[test]
+    fn diagnostics_are_kept_until_acknowledged() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+        let index = Arc::new(SharedIndex::new());
+
+        write_metadata(&config, "mail");
+        write_metadata(&config, "vpn");
+        fs::create_dir_all(&config.secrets_dir).unwrap();
+
+        assert_eq!(indexed(&config, &index), ["mail", "vpn"]);
+
+        let watcher = VaultWatcher::start(
+            Arc::clone(&config),
+            Arc::clone(&index),
+            Arc::new(RwLock::new(())),
+        )
+        .unwrap();
+        let wait_for_diagnostics = |count: usize| {
+            let deadline = Instant::now() + Duration::from_secs(5);
+
+            while watcher.diagnostics().0.len() < count
+                && Instant::now() < deadline
+            {
+                thread::sleep(Duration::from_millis(20));
+            }
+
+            // Let the rest of the write's events settle.
+            thread::sleep(DEBOUNCE * 2);
+
+            watcher.diagnostics()
+        };
+
+        // Neither secret matches the empty checksum its metadata records.
+        fs::write(config.secrets_dir.join("mail.pgp"), "changed").unwrap();
+
+        let (first, mark) = wait_for_diagnostics(1);
+
+        assert_eq!(first.len(), 1);
+        assert!(first[0].message.contains("'mail'"));
+
+        // Reading does not consume them, so concurrent calls see the same.
+        assert_eq!(watcher.diagnostics().0.len(), 1);
+
+        fs::write(config.secrets_dir.join("vpn.pgp"), "changed").unwrap();
+        wait_for_diagnostics(2);
+        watcher.acknowledge(mark);
+
+        let (rest, _) = watcher.diagnostics();
+
+        assert_eq!(rest.len(), 1);
+        assert!(rest[0].message.contains("'vpn'"));
+    }
+
+    #[test]
+    fn vault_directories_created_after_startup_are_watched() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+        let index = Arc::new(SharedIndex::new());
+        let _watcher = VaultWatcher::start(
+            Arc::clone(&config),
+            Arc::clone(&index),
+            Arc::new(RwLock::new(())),
+        )
+        .unwrap();
+
+        assert!(indexed(&config, &index).is_empty());
+
+        write_metadata(&config, "late/mail");
+
+        wait_for(&config, &index, &["late/mail"]);
+    }
+
+    #[test]
+    fn writes_already_in_the_index_do_not_block_readers() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = config(dir.path());
+        let index = Arc::new(SharedIndex::new());
+        let lock = Arc::new(RwLock::new(()));
+
+        write_metadata(&config, "mail");
+
+        assert_eq!(indexed(&config, &index), ["mail"]);
+
+        let _watcher = VaultWatcher::start(
+            Arc::clone(&config),
+            Arc::clone(&index),
+            Arc::clone(&lock),
+        )
+        .unwrap();
+        let reader = lock.blocking_read();
+
+        // Written and indexed the way passd writes a secret itself. Had the
+        // watcher queued for the write lock, new readers would wait behind it.
+        write_metadata(&config, "mail");
+        index.update(&config, &[Path::new("mail")]);
+        thread::sleep(DEBOUNCE * 3);
+
+        assert!(lock.try_read().is_ok());
+
+        drop(reader);
+    }
+}
@@ -0,0 +1,59 @@
+use jsonrpsee::server::{
+    HttpRequest, Methods, Server, StopHandle, serve_with_graceful_shutdown, ws,
+};
+use log::warn;
+use passd::utils::cancel::CancellationToken;
+use tokio::net::TcpListener;
+use tower::Service;
+
+/// Accepts connections until `stop_handle` fires. Every request carries a
+/// `CancellationToken` in its extensions; for WebSocket connections the
+/// token is cancelled as soon as the session closes, because jsonrpsee keeps
+/// running calls after their client has gone away.
+pub async fn serve(
+    listener: TcpListener,
+    methods: impl Into<Methods>,
+    stop_handle: StopHandle,
+) {
+    let builder = Server::builder().to_service_builder();
+    let methods = methods.into();
+
+    loop {
+        let stream = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("Failed to accept connection: {}", e);
+
+                    continue;
+                }
+            },
+            _ = stop_handle.clone().shutdown() => break,
+        };
+
+        let service =
+            builder.clone().build(methods.clone(), stop_handle.clone());
+        let connection =
+            tower::service_fn(move |mut request: HttpRequest<_>| {
+                let mut service = service.clone();
+                let cancel = CancellationToken::new();
+
+                if ws::is_upgrade_request(&request) {
+                    let closed = service.on_session_closed();
+                    let cancel = cancel.clone();
+
+                    tokio::spawn(async move {
+                        closed.await;
+                        cancel.cancel();
+                    });
+                }
+
+                request.extensions_mut().insert(cancel);
+
+                async move { service.call(request).await }
+            });
+        let stopped = stop_handle.clone().shutdown();
+
+        tokio::spawn(serve_with_graceful_shutdown(stream, connection, stopped));
+    }
+}
@@ -0,0 +1,51 @@
+use tokio_util::sync::CancellationToken as TokenInner;
+
+use crate::error::{Error, Result};
+
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(TokenInner);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a token that is cancelled together with this one, but can
+    /// also be cancelled on its own without affecting its parent.
+    pub fn child_token(&self) -> Self {
+        Self(self.0.child_token())
+    }
+
+    pub fn cancel(&self) {
+        self.0.cancel();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+
+        Ok(())
+    }
+
+    pub async fn cancelled(&self) {
+        self.0.cancelled().await
+    }
+
+    pub fn drop_guard(&self) -> DropGuard {
+        DropGuard(self.clone())
+    }
+}
+
+#[derive(Debug)]
+pub struct DropGuard(CancellationToken);
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
@@ -0,0 +1,316 @@
+use std::{
+    collections::BTreeMap,
+    fmt, io,
+    ops::{Deref, Range},
+    sync::{Mutex, OnceLock},
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use zeroize::{Zeroize, Zeroizing};
+
+pub struct SecretString {
+    inner: String,
+    locked: bool,
+}
+
+impl SecretString {
+    pub fn new(inner: String) -> Self {
+        let locked = lock_memory(inner.as_ptr(), inner.capacity());
+
+        Self { inner, locked }
+    }
+
+    pub fn from_utf8(bytes: Vec<u8>) -> Option<Self> {
+        match String::from_utf8(bytes) {
+            Ok(inner) => Some(Self::new(inner)),
+            Err(e) => {
+                e.into_bytes().zeroize();
+                None
+            }
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        let ptr = self.inner.as_ptr();
+        let len = self.inner.capacity();
+
+        self.inner.zeroize();
+
+        if self.locked {
+            unlock_memory(ptr, len);
+        }
+    }
+}
+
+impl Deref for SecretString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.inner
+    }
+}
+
+impl AsRef<[u8]> for SecretString {
+    fn as_ref(&self) -> &[u8] {
+        self.inner.as_bytes()
+    }
+}
+
+impl Clone for SecretString {
+    fn clone(&self) -> Self {
+        Self::new(self.inner.clone())
+    }
+}
+
+impl Default for SecretString {
+    fn default() -> Self {
+        Self::new(String::new())
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(inner: String) -> Self {
+        Self::new(inner)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(inner: &str) -> Self {
+        Self::new(inner.to_string())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretString([REDACTED])")
+    }
+}
+
+impl Serialize for SecretString {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.inner)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Self::new)
+    }
+}
+
+/// A growable buffer for assembling plaintext. A `Vec` frees outgrown
+/// allocations as they are; this one wipes them before letting go.
+#[derive(Default)]
+pub struct SecretBuffer {
+    inner: Zeroizing<Vec<u8>>,
+}
+
+impl SecretBuffer {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Zeroizing::new(Vec::with_capacity(capacity)),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    pub fn extend_from_slice(&mut self, bytes: &[u8]) {
+        if self.inner.capacity() - self.inner.len() < bytes.len() {
+            let mut grown = Zeroizing::new(Vec::with_capacity(
+                (self.inner.len() + bytes.len()).max(self.inner.capacity() * 2),
+            ));
+
+            grown.extend_from_slice(&self.inner);
+            self.inner = grown;
+        }
+
+        self.inner.extend_from_slice(bytes);
+    }
+
+    pub fn into_secret_string(mut self) -> Option<SecretString> {
+        SecretString::from_utf8(std::mem::take(&mut *self.inner))
+    }
+}
+
+impl io::Write for SecretBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl fmt::Write for SecretBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.extend_from_slice(s.as_bytes());
+
+        Ok(())
+    }
+}
+
+// `mlock` works on whole pages and does not nest: one `munlock` releases a
+// page however many secrets live on it. Pages are therefore counted, and only
+// unlocked once the last secret on them is dropped.
+static LOCKED_PAGES: Mutex<BTreeMap<usize, usize>> =
+    Mutex::new(BTreeMap::new());
+
+fn page_size() -> usize {
+    static PAGE_SIZE: OnceLock<usize> = OnceLock::new();
+
+    *PAGE_SIZE.get_or_init(|| {
+        // SAFETY: sysconf has no preconditions.
+        let size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+
+        usize::try_from(size)
+            .ok()
+            .filter(|&s| s > 0)
+            .unwrap_or(4096)
+    })
+}
+
+fn pages(ptr: *const u8, len: usize) -> Range<usize> {
+    let page_size = page_size();
+    let start = ptr as usize / page_size;
+    let end = (ptr as usize + len).div_ceil(page_size);
+
+    start..end
+}
+
+fn lock_memory(ptr: *const u8, len: usize) -> bool {
+    if len == 0 {
+        return false;
+    }
+
+    let mut locked_pages =
+        LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+
+    // SAFETY: the range is a live heap allocation owned by the caller.
+    let locked = unsafe { libc::mlock(ptr.cast(), len) } == 0;
+
+    if !locked {
+        log::debug!(
+            "Failed to lock secret buffer in memory: {}",
+            io::Error::last_os_error()
+        );
+
+        return false;
+    }
+
+    for page in pages(ptr, len) {
+        *locked_pages.entry(page).or_insert(0) += 1;
+    }
+
+    true
+}
+
+fn unlock_memory(ptr: *const u8, len: usize) {
+    let mut locked_pages =
+        LOCKED_PAGES.lock().unwrap_or_else(|e| e.into_inner());
+    let page_size = page_size();
+
+    for page in pages(ptr, len) {
+        let Some(count) = locked_pages.get_mut(&page) else {
+            continue;
+        };
+
+        *count -= 1;
+
+        if *count == 0 {
+            locked_pages.remove(&page);
+
+            // SAFETY: the page was locked by `lock_memory` and no live
+            // secret is left on it.
+            unsafe {
+                libc::munlock(
+                    (page * page_size) as *const libc::c_void,
+                    page_size,
+                );
+            }
+        }
+    }
+}
+
+pub fn disable_core_dumps() -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+
+    // SAFETY: `limit` is a valid, initialized rlimit structure.
+    if unsafe { libc::setrlimit(libc::RLIMIT_CORE, &limit) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    #[cfg(target_os = "linux")]
+    // SAFETY: PR_SET_DUMPABLE takes a single integer argument.
+    if unsafe { libc::prctl(libc::PR_SET_DUMPABLE, 0) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lock_count(page: usize) -> usize {
+        LOCKED_PAGES
+            .lock()
+            .unwrap()
+            .get(&page)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn pages_stay_locked_until_the_last_range_on_them_is_unlocked() {
+        let buffer = vec![0u8; page_size() * 3];
+        // A page wholly inside the buffer, which no other test can touch.
+        let page = pages(buffer.as_ptr(), buffer.len()).start + 1;
+        let first = (page * page_size()) as *const u8;
+        let second = first.wrapping_add(100);
+
+        // Without the privilege to lock memory there is nothing to count.
+        if !lock_memory(first, 16) {
+            return;
+        }
+
+        assert!(lock_memory(second, 16));
+        assert_eq!(lock_count(page), 2);
+
+        unlock_memory(first, 16);
+        assert_eq!(lock_count(page), 1);
+
+        unlock_memory(second, 16);
+        assert_eq!(lock_count(page), 0);
+    }
+
+    #[test]
+    fn secret_buffers_keep_their_content_when_growing() {
+        let mut buffer = SecretBuffer::with_capacity(2);
+
+        for _ in 0..100 {
+            buffer.extend_from_slice(b"abc");
+        }
+
+        assert_eq!(&*buffer.into_secret_string().unwrap(), "abc".repeat(100));
+    }
+}
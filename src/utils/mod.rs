@@ -1,3 +1,6 @@
+pub mod cancel;
 pub mod checksum;
 pub mod fs;
 pub mod logger;
+pub mod memory;
+pub mod time;
@@ -0,0 +1,46 @@
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+
+pub fn parse_duration(input: &str) -> Option<Duration> {
+    let input = input.trim();
+    let split = input.find(|c: char| !c.is_ascii_digit())?;
+    let (amount, unit) = input.split_at(split);
+    let amount: i64 = amount.parse().ok()?;
+
+    match unit {
+        "s" => Duration::try_seconds(amount),
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        "y" => Duration::try_days(amount.checked_mul(365)?),
+        _ => None,
+    }
+}
+
+pub fn parse_datetime(input: &str) -> Option<DateTime<Utc>> {
+    let input = input.trim();
+
+    if let Some(relative) = input.strip_prefix("now") {
+        let now = Utc::now();
+
+        return match relative.chars().next() {
+            None => Some(now),
+            Some('-') => {
+                now.checked_sub_signed(parse_duration(&relative[1..])?)
+            }
+            Some('+') => {
+                now.checked_add_signed(parse_duration(&relative[1..])?)
+            }
+            Some(_) => None,
+        };
+    }
+
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(input) {
+        return Some(datetime.with_timezone(&Utc));
+    }
+
+    NaiveDate::parse_from_str(input, "%Y-%m-%d")
+        .ok()?
+        .and_hms_opt(0, 0, 0)
+        .map(|datetime| datetime.and_utc())
+}